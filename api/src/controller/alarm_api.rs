@@ -0,0 +1,125 @@
+use common_lib::alarm_utils::{
+    ack_alarm, close_alarm, comment_alarm, list_open_alarms, AlarmError, AlarmQuery,
+};
+use common_lib::models::{AlarmRecord, AlarmSeverity};
+use common_lib::mongo_utils::MongoDBManager;
+use log::error;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::{get, post, State};
+use serde::Deserialize;
+
+/// 确认、关闭、备注报警的请求体
+#[derive(Debug, Deserialize)]
+pub struct AlarmOperation {
+    pub operator: String,
+    pub comment: Option<String>,
+}
+
+/// 查询未关闭的报警, 例如 /alarm/open?device_uid=1&severity=critical&start=1730000000
+#[get("/alarm/open?<device_uid>&<severity>&<start>&<end>&<limit>")]
+pub async fn open_alarms(
+    mongo: &State<MongoDBManager>,
+    device_uid: Option<String>,
+    severity: Option<String>,
+    start: Option<i64>,
+    end: Option<i64>,
+    limit: Option<i64>,
+) -> Result<Json<Vec<AlarmRecord>>, status::Custom<String>> {
+    let severity = match severity {
+        Some(s) => Some(
+            s.parse::<AlarmSeverity>()
+                .map_err(|e| status::Custom(Status::BadRequest, e))?,
+        ),
+        None => None,
+    };
+    let query = AlarmQuery {
+        device_uid,
+        severity,
+        start,
+        end,
+        limit,
+    };
+    let records = list_open_alarms(mongo, &query)
+        .await
+        .map_err(server_error)?;
+    Ok(Json(records))
+}
+
+#[post("/alarm/<id>/ack", data = "<operation>")]
+pub async fn ack(
+    mongo: &State<MongoDBManager>,
+    id: &str,
+    operation: Json<AlarmOperation>,
+) -> Result<Status, status::Custom<String>> {
+    let updated = ack_alarm(
+        mongo,
+        id,
+        operation.operator.as_str(),
+        operation.comment.as_deref(),
+        common_lib::time_utils::local_to_utc(),
+    )
+    .await
+    .map_err(server_error)?;
+    Ok(updated_status(updated))
+}
+
+#[post("/alarm/<id>/close", data = "<operation>")]
+pub async fn close(
+    mongo: &State<MongoDBManager>,
+    id: &str,
+    operation: Json<AlarmOperation>,
+) -> Result<Status, status::Custom<String>> {
+    let updated = close_alarm(
+        mongo,
+        id,
+        operation.operator.as_str(),
+        operation.comment.as_deref(),
+        common_lib::time_utils::local_to_utc(),
+    )
+    .await
+    .map_err(server_error)?;
+    Ok(updated_status(updated))
+}
+
+#[post("/alarm/<id>/comment", data = "<operation>")]
+pub async fn comment(
+    mongo: &State<MongoDBManager>,
+    id: &str,
+    operation: Json<AlarmOperation>,
+) -> Result<Status, status::Custom<String>> {
+    let text = match operation.comment.as_deref() {
+        Some(text) if !text.is_empty() => text,
+        _ => {
+            return Err(status::Custom(
+                Status::BadRequest,
+                "comment 不能为空".to_string(),
+            ))
+        }
+    };
+    let updated = comment_alarm(
+        mongo,
+        id,
+        operation.operator.as_str(),
+        text,
+        common_lib::time_utils::local_to_utc(),
+    )
+    .await
+    .map_err(server_error)?;
+    Ok(updated_status(updated))
+}
+
+/// 报警不存在或当前状态不允许该操作时返回 409
+fn updated_status(updated: bool) -> Status {
+    if updated {
+        Status::Ok
+    } else {
+        Status::Conflict
+    }
+}
+
+fn server_error(e: AlarmError) -> status::Custom<String> {
+    error!("报警操作失败: {}", e);
+    status::Custom(Status::InternalServerError, e.to_string())
+}
@@ -0,0 +1,21 @@
+use common_lib::calc_schedule::{list_calc_schedule, CalcScheduleEntry};
+use common_lib::redis_pool_utils::RedisOp;
+use common_lib::time_utils::local_to_utc;
+use log::error;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::{get, State};
+
+/// 计算规则的下一次执行时间, 按时间升序
+#[get("/calc/schedule")]
+pub async fn calc_schedule(
+    redis: &State<RedisOp>,
+) -> Result<Json<Vec<CalcScheduleEntry>>, status::Custom<String>> {
+    list_calc_schedule(redis, local_to_utc())
+        .map(Json)
+        .map_err(|e| {
+            error!("查询计算规则调度失败: {}", e);
+            status::Custom(Status::InternalServerError, e.to_string())
+        })
+}
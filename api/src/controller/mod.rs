@@ -1 +1,4 @@
+pub mod alarm_api;
+pub mod calc_api;
 pub mod demo_api;
+pub mod script_api;
@@ -0,0 +1,25 @@
+use common_lib::redis_pool_utils::RedisOp;
+use common_lib::script_dry_run::{dry_run, DryRunReport, DryRunRequest};
+use log::error;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::{post, State};
+
+/// 试运行解析脚本或报警脚本, 不写入数据也不发送消息
+#[post("/script/dry_run", data = "<request>")]
+pub async fn dry_run_script(
+    redis: &State<RedisOp>,
+    request: Json<DryRunRequest>,
+) -> Result<Json<DryRunReport>, status::Custom<String>> {
+    let redis = redis.inner().clone();
+    let request = request.into_inner();
+    // 脚本执行是同步的, 放到阻塞线程中避免占用请求线程
+    let report = rocket::tokio::task::spawn_blocking(move || dry_run(&request, Some(&redis)))
+        .await
+        .map_err(|e| {
+            error!("脚本试运行失败: {}", e);
+            status::Custom(Status::InternalServerError, e.to_string())
+        })?;
+    Ok(Json(report))
+}
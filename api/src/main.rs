@@ -1,4 +1,5 @@
 use common_lib::config::{get_config, read_config, read_config_tb};
+use common_lib::mongo_utils::MongoFairing;
 use common_lib::mysql_utils::MysqlOp;
 use common_lib::rabbit_utils::{init_rabbitmq_with_config, RabbitMQFairing};
 use common_lib::redis_handler::init_redis;
@@ -26,6 +27,9 @@ fn rocket() -> _ {
         .attach(RabbitMQFairing {
             config: config1.mq_config.clone(),
         })
+        .attach(MongoFairing {
+            config: config1.mongo_config.clone().unwrap(),
+        })
         .manage(redis_op)
         .manage(mysql_op)
         .manage(config1.clone())
@@ -34,5 +38,16 @@ fn rocket() -> _ {
             log_level: rocket::config::LogLevel::Off,
             ..Default::default()
         })
-        .mount("/", routes![crate::controller::demo_api::index]) // 挂载路由
+        .mount(
+            "/",
+            routes![
+                crate::controller::demo_api::index,
+                crate::controller::alarm_api::open_alarms,
+                crate::controller::alarm_api::ack,
+                crate::controller::alarm_api::close,
+                crate::controller::alarm_api::comment,
+                crate::controller::calc_api::calc_schedule,
+                crate::controller::script_api::dry_run_script,
+            ],
+        ) // 挂载路由
 }
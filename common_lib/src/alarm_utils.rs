@@ -0,0 +1,253 @@
+use crate::models::{AlarmComment, AlarmLifecycle, AlarmRecord, AlarmSeverity};
+use crate::mongo_utils::MongoDBManager;
+use futures_util::StreamExt;
+use mongodb::bson::{self, doc, Document};
+use mongodb::error::{ErrorKind, WriteError, WriteFailure};
+use std::error::Error;
+
+/// 报警记录集合
+pub const ALARM_COLLECTION: &str = "alarm";
+
+pub type AlarmError = Box<dyn Error + Send + Sync>;
+
+/// 报警记录查询条件, fired_at 落在 [start, end] 内
+#[derive(Debug, Default, Clone)]
+pub struct AlarmQuery {
+    pub device_uid: Option<String>,
+    pub severity: Option<AlarmSeverity>,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// 报警记录ID, 同一报警状态键每次触发生成一条记录
+pub fn alarm_record_id(state_key: &str, fired_at: i64) -> String {
+    format!("{}:{}", state_key, fired_at)
+}
+
+pub async fn insert_alarm_record(
+    mongo: &MongoDBManager,
+    record: &AlarmRecord,
+) -> Result<(), AlarmError> {
+    match mongo
+        .db
+        .collection::<AlarmRecord>(ALARM_COLLECTION)
+        .insert_one(record)
+        .await
+    {
+        Ok(_) => Ok(()),
+        // 处理失败重新投递的消息会再次写入同一 ID 的记录
+        Err(e) if is_duplicate_key(&e) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn is_duplicate_key(err: &mongodb::error::Error) -> bool {
+    matches!(
+        err.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(WriteError { code: 11000, .. }))
+    )
+}
+
+/// 报警恢复, 更新恢复时间与持续时间
+pub async fn resolve_alarm_record(
+    mongo: &MongoDBManager,
+    id: &str,
+    resolved_at: i64,
+    duration: i64,
+) -> Result<bool, AlarmError> {
+    let result = mongo
+        .db
+        .collection::<Document>(ALARM_COLLECTION)
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": {
+                "status": "RESOLVED",
+                "resolved_at": resolved_at,
+                "duration": duration,
+            } },
+        )
+        .await?;
+    Ok(result.matched_count > 0)
+}
+
+/// 确认报警, 只有待处理的报警可以确认
+pub async fn ack_alarm(
+    mongo: &MongoDBManager,
+    id: &str,
+    operator: &str,
+    comment: Option<&str>,
+    time: i64,
+) -> Result<bool, AlarmError> {
+    let filter = doc! { "_id": id, "lifecycle": lifecycle_bson(AlarmLifecycle::Open)? };
+    let mut update = doc! { "$set": {
+        "lifecycle": lifecycle_bson(AlarmLifecycle::Acknowledged)?,
+        "acked_by": operator,
+        "acked_at": time,
+    } };
+    push_comment(&mut update, operator, comment, time)?;
+    update_alarm(mongo, filter, update).await
+}
+
+/// 关闭报警, 待处理和已确认的报警都可以关闭
+pub async fn close_alarm(
+    mongo: &MongoDBManager,
+    id: &str,
+    operator: &str,
+    comment: Option<&str>,
+    time: i64,
+) -> Result<bool, AlarmError> {
+    let filter =
+        doc! { "_id": id, "lifecycle": { "$ne": lifecycle_bson(AlarmLifecycle::Closed)? } };
+    let mut update = doc! { "$set": {
+        "lifecycle": lifecycle_bson(AlarmLifecycle::Closed)?,
+        "closed_by": operator,
+        "closed_at": time,
+    } };
+    push_comment(&mut update, operator, comment, time)?;
+    update_alarm(mongo, filter, update).await
+}
+
+pub async fn comment_alarm(
+    mongo: &MongoDBManager,
+    id: &str,
+    operator: &str,
+    comment: &str,
+    time: i64,
+) -> Result<bool, AlarmError> {
+    let mut update = Document::new();
+    push_comment(&mut update, operator, Some(comment), time)?;
+    update_alarm(mongo, doc! { "_id": id }, update).await
+}
+
+pub async fn get_alarm(
+    mongo: &MongoDBManager,
+    id: &str,
+) -> Result<Option<AlarmRecord>, AlarmError> {
+    let record = mongo
+        .db
+        .collection::<AlarmRecord>(ALARM_COLLECTION)
+        .find_one(doc! { "_id": id })
+        .await?;
+    Ok(record)
+}
+
+/// 查询未关闭的报警, 按触发时间倒序
+pub async fn list_open_alarms(
+    mongo: &MongoDBManager,
+    query: &AlarmQuery,
+) -> Result<Vec<AlarmRecord>, AlarmError> {
+    let mut cursor = mongo
+        .db
+        .collection::<AlarmRecord>(ALARM_COLLECTION)
+        .find(open_alarm_filter(query)?)
+        .sort(doc! { "fired_at": -1 })
+        .limit(query.limit.unwrap_or(100))
+        .await?;
+
+    let mut records = Vec::new();
+    while let Some(record) = cursor.next().await {
+        records.push(record?);
+    }
+    Ok(records)
+}
+
+pub fn open_alarm_filter(query: &AlarmQuery) -> Result<Document, AlarmError> {
+    let mut filter = doc! { "lifecycle": { "$ne": lifecycle_bson(AlarmLifecycle::Closed)? } };
+    if let Some(device_uid) = &query.device_uid {
+        filter.insert("device_uid", device_uid.as_str());
+    }
+    if let Some(severity) = query.severity {
+        filter.insert("severity", severity.as_str());
+    }
+    let mut fired_at = Document::new();
+    if let Some(start) = query.start {
+        fired_at.insert("$gte", start);
+    }
+    if let Some(end) = query.end {
+        fired_at.insert("$lte", end);
+    }
+    if !fired_at.is_empty() {
+        filter.insert("fired_at", fired_at);
+    }
+    Ok(filter)
+}
+
+async fn update_alarm(
+    mongo: &MongoDBManager,
+    filter: Document,
+    update: Document,
+) -> Result<bool, AlarmError> {
+    let result = mongo
+        .db
+        .collection::<Document>(ALARM_COLLECTION)
+        .update_one(filter, update)
+        .await?;
+    Ok(result.matched_count > 0)
+}
+
+fn push_comment(
+    update: &mut Document,
+    operator: &str,
+    comment: Option<&str>,
+    time: i64,
+) -> Result<(), AlarmError> {
+    if let Some(comment) = comment {
+        let comment = AlarmComment {
+            operator: operator.to_string(),
+            comment: comment.to_string(),
+            time,
+        };
+        update.insert("$push", doc! { "comments": bson::to_bson(&comment)? });
+    }
+    Ok(())
+}
+
+fn lifecycle_bson(lifecycle: AlarmLifecycle) -> Result<bson::Bson, AlarmError> {
+    Ok(bson::to_bson(&lifecycle)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_alarm_filter() {
+        let filter = open_alarm_filter(&AlarmQuery {
+            device_uid: Some("1".to_string()),
+            severity: Some(AlarmSeverity::Critical),
+            start: Some(100),
+            end: None,
+            limit: None,
+        })
+        .unwrap();
+        assert_eq!(
+            filter,
+            doc! {
+                "lifecycle": { "$ne": "CLOSED" },
+                "device_uid": "1",
+                "severity": "critical",
+                "fired_at": { "$gte": 100_i64 },
+            }
+        );
+
+        let filter = open_alarm_filter(&AlarmQuery::default()).unwrap();
+        assert_eq!(filter, doc! { "lifecycle": { "$ne": "CLOSED" } });
+    }
+
+    #[test]
+    fn test_push_comment() {
+        let mut update = Document::new();
+        push_comment(&mut update, "admin", None, 100).unwrap();
+        assert!(update.is_empty());
+
+        push_comment(&mut update, "admin", Some("已处理"), 100).unwrap();
+        let comment = update
+            .get_document("$push")
+            .unwrap()
+            .get_document("comments")
+            .unwrap();
+        assert_eq!(comment.get_str("comment").unwrap(), "已处理");
+        assert_eq!(comment.get_i64("time").unwrap(), 100);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::models::{CalcCache, CatchUpPolicy};
+use crate::redis_pool_utils::RedisOp;
+use chrono::{TimeZone, Utc};
+use cron::Schedule;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::str::FromStr;
+
+/// 计算规则调度队列, 成员为 {"id":规则id}, 分值为下一次执行时间(秒级时间戳)
+pub const CALC_QUEUE: &str = "calc_queue";
+
+/// 计算规则缓存, 字段为规则 id, 值为 CalcCache
+pub const CALC_CACHE: &str = "calc_cache";
+
+/// 单个规则一次最多补偿的执行次数
+pub const MAX_CATCH_UP: usize = 100;
+
+/// 延迟不超过该秒数的执行不视为错过
+pub const MISFIRE_GRACE_SECS: i64 = 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CalcScheduleEntry {
+    pub id: u64,
+    pub next_time: i64,
+    pub cron: Option<String>,
+    pub catch_up: Option<CatchUpPolicy>,
+    pub overdue: bool, // 已到执行时间但尚未被调度器取走
+}
+
+pub fn parse_schedule(cron_expr: &str) -> Result<Schedule, Box<dyn Error>> {
+    Schedule::from_str(cron_expr)
+        .map_err(|e| format!("cron 表达式无效 {}: {}", cron_expr, e).into())
+}
+
+/// after 之后(不含)的下一次执行时间
+pub fn next_run(schedule: &Schedule, after: i64) -> Option<i64> {
+    let after = Utc.timestamp_opt(after, 0).single()?;
+    schedule.after(&after).next().map(|t| t.timestamp())
+}
+
+/// 计算本轮需要执行的时间点, due 为队列中的执行时间, 其后 now 之前的执行视为错过
+pub fn due_runs(schedule: &Schedule, due: i64, now: i64, policy: CatchUpPolicy) -> Vec<i64> {
+    if due > now {
+        return vec![];
+    }
+    let mut runs = vec![due];
+    let mut last = due;
+    while let Some(t) = next_run(schedule, last) {
+        if t > now {
+            break;
+        }
+        runs.push(t);
+        last = t;
+    }
+
+    let latest = *runs.last().unwrap();
+    match policy {
+        CatchUpPolicy::All => {
+            if runs.len() > MAX_CATCH_UP {
+                warn!("错过 {} 次执行, 只补偿最近 {} 次", runs.len(), MAX_CATCH_UP);
+                runs.drain(..runs.len() - MAX_CATCH_UP);
+            }
+            runs
+        }
+        CatchUpPolicy::Latest => vec![latest],
+        CatchUpPolicy::Skip => {
+            if now - latest <= MISFIRE_GRACE_SECS {
+                vec![latest]
+            } else {
+                vec![]
+            }
+        }
+    }
+}
+
+/// 解析队列成员中的规则 id
+pub fn queue_member_id(member: &str) -> Option<u64> {
+    let map: HashMap<String, i64> = serde_json::from_str(member).ok()?;
+    map.get("id").and_then(|id| u64::try_from(*id).ok())
+}
+
+pub fn queue_member(id: u64) -> String {
+    format!(r#"{{"id":{}}}"#, id)
+}
+
+pub fn get_calc_cache(id: u64, redis: &RedisOp) -> Result<Option<CalcCache>, Box<dyn Error>> {
+    match redis.get_hash(CALC_CACHE, id.to_string().as_str())? {
+        Some(value) => Ok(Some(serde_json::from_str(&value)?)),
+        None => Ok(None),
+    }
+}
+
+/// 按执行时间升序列出所有计算规则的下一次执行时间
+pub fn list_calc_schedule(
+    redis: &RedisOp,
+    now: i64,
+) -> Result<Vec<CalcScheduleEntry>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    for (member, score) in redis.get_zset(CALC_QUEUE)? {
+        let id = match queue_member_id(&member) {
+            Some(id) => id,
+            None => continue,
+        };
+        let cache = get_calc_cache(id, redis).unwrap_or(None);
+        entries.push(CalcScheduleEntry {
+            id,
+            next_time: score as i64,
+            cron: cache.as_ref().map(|c| c.cron.clone()),
+            catch_up: cache.as_ref().map(|c| c.catch_up),
+            overdue: (score as i64) <= now,
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-11-05 12:00:00 UTC
+    const NOON: i64 = 1730808000;
+
+    fn every_minute() -> Schedule {
+        parse_schedule("0 * * * * *").unwrap()
+    }
+
+    #[test]
+    fn test_next_run() {
+        let daily = parse_schedule("0 0 12 * * *").unwrap();
+        assert_eq!(next_run(&daily, NOON - 60), Some(NOON));
+        assert_eq!(next_run(&daily, NOON), Some(NOON + 86400));
+        assert!(parse_schedule("invalid cron expression").is_err());
+    }
+
+    #[test]
+    fn test_due_runs() {
+        let schedule = every_minute();
+
+        assert!(due_runs(&schedule, NOON + 60, NOON, CatchUpPolicy::All).is_empty());
+
+        // 按时执行
+        for policy in [
+            CatchUpPolicy::All,
+            CatchUpPolicy::Latest,
+            CatchUpPolicy::Skip,
+        ] {
+            assert_eq!(due_runs(&schedule, NOON, NOON + 1, policy), vec![NOON]);
+        }
+
+        // 错过 3 分钟
+        let now = NOON + 190;
+        assert_eq!(
+            due_runs(&schedule, NOON, now, CatchUpPolicy::All),
+            vec![NOON, NOON + 60, NOON + 120, NOON + 180]
+        );
+        assert_eq!(
+            due_runs(&schedule, NOON, now, CatchUpPolicy::Latest),
+            vec![NOON + 180]
+        );
+        assert_eq!(
+            due_runs(&schedule, NOON, now, CatchUpPolicy::Skip),
+            vec![NOON + 180]
+        );
+
+        // 整点任务停机 2 小时, skip 不补偿
+        let hourly = parse_schedule("0 0 * * * *").unwrap();
+        let now = NOON + 7200 + 300;
+        assert!(due_runs(&hourly, NOON, now, CatchUpPolicy::Skip).is_empty());
+        assert_eq!(
+            due_runs(&hourly, NOON, now, CatchUpPolicy::Latest),
+            vec![NOON + 7200]
+        );
+    }
+
+    #[test]
+    fn test_due_runs_limit() {
+        let runs = due_runs(&every_minute(), NOON, NOON + 86400, CatchUpPolicy::All);
+        assert_eq!(runs.len(), MAX_CATCH_UP);
+        assert_eq!(*runs.last().unwrap(), NOON + 86400);
+    }
+
+    #[test]
+    fn test_queue_member() {
+        assert_eq!(queue_member_id(&queue_member(12)), Some(12));
+        assert_eq!(queue_member_id(r#"{"id":-1}"#), None);
+        assert_eq!(queue_member_id("12"), None);
+    }
+}
@@ -38,6 +38,37 @@ pub struct InfluxConfig {
     pub token: Option<String>,
     pub org: Option<String>,
     pub bucket: Option<String>,
+    #[serde(default)]
+    pub writer: InfluxWriterConfig,
+}
+
+/// 共享写入器的批量与重试配置
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct InfluxWriterConfig {
+    pub batch_size: usize,         // 单个 bucket 缓存达到该数量时立即写入
+    pub flush_interval_ms: u64,    // 定时写入间隔
+    pub queue_size: usize,         // 内存队列可缓存的数据点数量, 已满时丢弃新数据点
+    pub max_retries: u32,          // 写入失败后的重试次数
+    pub retry_backoff_ms: u64,     // 首次重试等待时间, 之后按倍数增长
+    pub max_backoff_ms: u64,       // 重试等待时间上限, 也是写入失败后的熔断时长
+    pub spill_dir: Option<String>, // 写入失败或熔断期间落盘的目录, 为空时丢弃
+    pub max_spill_mb: u64,         // 落盘文件大小上限
+}
+
+impl Default for InfluxWriterConfig {
+    fn default() -> Self {
+        InfluxWriterConfig {
+            batch_size: 5000,
+            flush_interval_ms: 1000,
+            queue_size: 100_000,
+            max_retries: 3,
+            retry_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            spill_dir: None,
+            max_spill_mb: 1024,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
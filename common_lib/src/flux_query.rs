@@ -0,0 +1,626 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// 转义 Flux 字符串字面量, 用于 bucket、measurement、字段名和标签值
+pub fn escape_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => escaped.push_str(r"\\"),
+            '"' => escaped.push_str(r#"\""#),
+            '\n' => escaped.push_str(r"\n"),
+            '\r' => escaped.push_str(r"\r"),
+            '\t' => escaped.push_str(r"\t"),
+            // ${ 在 Flux 字符串中表示插值
+            '$' if chars.peek() == Some(&'{') => escaped.push_str(r"\$"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// 列访问表达式 r["列名"]
+fn column(name: &str) -> String {
+    format!("r[{}]", escape_string(name))
+}
+
+fn string_array(values: &[String]) -> String {
+    let items: Vec<String> = values.iter().map(|v| escape_string(v)).collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// 允许的聚合函数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Mean,
+    Sum,
+    Min,
+    Max,
+    Count,
+    First,
+    Last,
+    Median,
+    Spread,
+    Stddev,
+}
+
+impl Aggregate {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Aggregate::Mean => "mean",
+            Aggregate::Sum => "sum",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::Count => "count",
+            Aggregate::First => "first",
+            Aggregate::Last => "last",
+            Aggregate::Median => "median",
+            Aggregate::Spread => "spread",
+            Aggregate::Stddev => "stddev",
+        }
+    }
+}
+
+impl FromStr for Aggregate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "mean" => Ok(Aggregate::Mean),
+            "sum" => Ok(Aggregate::Sum),
+            "min" => Ok(Aggregate::Min),
+            "max" => Ok(Aggregate::Max),
+            "count" => Ok(Aggregate::Count),
+            "first" => Ok(Aggregate::First),
+            "last" => Ok(Aggregate::Last),
+            "median" => Ok(Aggregate::Median),
+            "spread" => Ok(Aggregate::Spread),
+            "stddev" => Ok(Aggregate::Stddev),
+            _ => Err(format!("不支持的聚合函数: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 查询时间范围, 绝对时间为秒级时间戳, 相对时间为距当前的秒数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeRange {
+    Absolute { start: i64, stop: i64 },
+    Relative { start: u64, stop: Option<u64> },
+}
+
+impl TimeRange {
+    fn to_flux(self) -> Result<String, Box<dyn Error>> {
+        match self {
+            TimeRange::Absolute { start, stop } => {
+                if start >= stop {
+                    return Err(format!("开始时间 {} 必须早于结束时间 {}", start, stop).into());
+                }
+                Ok(format!("range(start: {}, stop: {})", start, stop))
+            }
+            TimeRange::Relative { start, stop } => match stop {
+                Some(stop) if stop >= start => {
+                    Err(format!("开始时间 -{}s 必须早于结束时间 -{}s", start, stop).into())
+                }
+                Some(stop) => Ok(format!("range(start: -{}s, stop: -{}s)", start, stop)),
+                None if start == 0 => Err("相对时间范围不能为 0".into()),
+                None => Ok(format!("range(start: -{}s)", start)),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TagOp {
+    Eq,
+    Ne,
+}
+
+/// 标签过滤, Eq 时满足任意一个值, Ne 时排除所有值
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagFilter {
+    pub tag: String,
+    pub op: TagOp,
+    pub values: Vec<String>,
+}
+
+impl TagFilter {
+    fn to_flux(&self) -> String {
+        let (op, join) = match self.op {
+            TagOp::Eq => ("==", " or "),
+            TagOp::Ne => ("!=", " and "),
+        };
+        let conditions: Vec<String> = self
+            .values
+            .iter()
+            .map(|v| format!("{} {} {}", column(&self.tag), op, escape_string(v)))
+            .collect();
+        format!("|> filter(fn: (r) => {})", conditions.join(join))
+    }
+}
+
+/// 聚合窗口为空时的填充方式, 需配合 create_empty 使用
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fill {
+    Previous,
+    Value(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Window {
+    every: u64,
+    create_empty: bool,
+}
+
+/// Flux 查询构造器, 所有名称和值都经过转义, 聚合函数只能从 Aggregate 中选择
+///
+/// 多个聚合函数时每个函数输出一个以函数名命名的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct FluxQuery {
+    bucket: String,
+    range: TimeRange,
+    measurement: Option<String>,
+    fields: Vec<String>,
+    tags: Vec<TagFilter>,
+    group_by: Vec<String>,
+    window: Option<Window>,
+    aggregates: Vec<Aggregate>,
+    fill: Option<Fill>,
+    pivot: bool,
+    limit: Option<(u64, u64)>,
+}
+
+impl FluxQuery {
+    pub fn new(bucket: &str, range: TimeRange) -> Self {
+        FluxQuery {
+            bucket: bucket.to_string(),
+            range,
+            measurement: None,
+            fields: vec![],
+            tags: vec![],
+            group_by: vec![],
+            window: None,
+            aggregates: vec![],
+            fill: None,
+            pivot: false,
+            limit: None,
+        }
+    }
+
+    pub fn measurement(mut self, measurement: &str) -> Self {
+        self.measurement = Some(measurement.to_string());
+        self
+    }
+
+    /// 多个字段之间为或关系
+    pub fn field(mut self, field: &str) -> Self {
+        self.fields.push(field.to_string());
+        self
+    }
+
+    pub fn tag_in(mut self, tag: &str, values: &[&str]) -> Self {
+        self.tags.push(TagFilter {
+            tag: tag.to_string(),
+            op: TagOp::Eq,
+            values: values.iter().map(|v| v.to_string()).collect(),
+        });
+        self
+    }
+
+    pub fn tag_eq(self, tag: &str, value: &str) -> Self {
+        self.tag_in(tag, &[value])
+    }
+
+    pub fn tag_ne(mut self, tag: &str, value: &str) -> Self {
+        self.tags.push(TagFilter {
+            tag: tag.to_string(),
+            op: TagOp::Ne,
+            values: vec![value.to_string()],
+        });
+        self
+    }
+
+    pub fn group_by(mut self, tag: &str) -> Self {
+        self.group_by.push(tag.to_string());
+        self
+    }
+
+    /// 按 every 秒的窗口聚合, 未设置时聚合函数作用于整个时间范围
+    pub fn window(mut self, every: u64, create_empty: bool) -> Self {
+        self.window = Some(Window {
+            every,
+            create_empty,
+        });
+        self
+    }
+
+    pub fn aggregate(mut self, aggregate: Aggregate) -> Self {
+        self.aggregates.push(aggregate);
+        self
+    }
+
+    pub fn fill(mut self, fill: Fill) -> Self {
+        self.fill = Some(fill);
+        self
+    }
+
+    /// 将字段转为列, 每个时间点一行
+    pub fn pivot(mut self) -> Self {
+        self.pivot = true;
+        self
+    }
+
+    pub fn limit(mut self, n: u64, offset: u64) -> Self {
+        self.limit = Some((n, offset));
+        self
+    }
+
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.bucket.is_empty() {
+            return Err("bucket 不能为空".into());
+        }
+        if self.measurement.as_deref() == Some("") {
+            return Err("measurement 不能为空".into());
+        }
+        let names = self
+            .fields
+            .iter()
+            .chain(self.group_by.iter())
+            .chain(self.tags.iter().map(|t| &t.tag));
+        for name in names {
+            if name.is_empty() {
+                return Err("字段名和标签名不能为空".into());
+            }
+        }
+        if let Some(tag) = self.tags.iter().find(|t| t.values.is_empty()) {
+            return Err(format!("标签 {} 没有过滤值", tag.tag).into());
+        }
+        for (i, aggregate) in self.aggregates.iter().enumerate() {
+            if self.aggregates[..i].contains(aggregate) {
+                return Err(format!("聚合函数 {} 重复", aggregate).into());
+            }
+        }
+        match self.window {
+            Some(window) if window.every == 0 => return Err("聚合窗口不能为 0".into()),
+            Some(_) if self.aggregates.is_empty() => {
+                return Err("聚合窗口需要至少一个聚合函数".into())
+            }
+            _ => {}
+        }
+        match self.fill {
+            Some(_) if !self.window.map(|w| w.create_empty).unwrap_or(false) => {
+                return Err("填充需要聚合窗口并开启 create_empty".into())
+            }
+            Some(Fill::Value(v)) if !v.is_finite() => {
+                return Err(format!("填充值无效: {}", v).into())
+            }
+            _ => {}
+        }
+        if let Some((0, _)) = self.limit {
+            return Err("limit 必须大于 0".into());
+        }
+        Ok(())
+    }
+
+    /// 聚合之前的公共部分
+    fn source(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut lines = vec![
+            format!("from(bucket: {})", escape_string(&self.bucket)),
+            format!("|> {}", self.range.to_flux()?),
+        ];
+        if let Some(measurement) = &self.measurement {
+            lines.push(format!(
+                "|> filter(fn: (r) => {} == {})",
+                column("_measurement"),
+                escape_string(measurement)
+            ));
+        }
+        if !self.fields.is_empty() {
+            let conditions: Vec<String> = self
+                .fields
+                .iter()
+                .map(|f| format!("{} == {}", column("_field"), escape_string(f)))
+                .collect();
+            lines.push(format!("|> filter(fn: (r) => {})", conditions.join(" or ")));
+        }
+        for tag in &self.tags {
+            lines.push(tag.to_flux());
+        }
+        if !self.group_by.is_empty() {
+            lines.push(format!(
+                "|> group(columns: {})",
+                string_array(&self.group_by)
+            ));
+        }
+        Ok(lines)
+    }
+
+    /// 聚合之后的公共部分
+    fn finish(&self, lines: &mut Vec<String>) {
+        match self.fill {
+            Some(Fill::Previous) => lines.push("|> fill(usePrevious: true)".to_string()),
+            Some(Fill::Value(v)) => lines.push(format!("|> fill(value: {:?})", v)),
+            None => {}
+        }
+        if self.pivot {
+            lines.push(
+                r#"|> pivot(rowKey: ["_time"], columnKey: ["_field"], valueColumn: "_value")"#
+                    .to_string(),
+            );
+        }
+        if let Some((n, offset)) = self.limit {
+            lines.push(format!("|> limit(n: {}, offset: {})", n, offset));
+        }
+    }
+
+    fn aggregate_line(&self, aggregate: Aggregate) -> String {
+        match self.window {
+            Some(window) => format!(
+                "|> aggregateWindow(every: {}s, fn: {}, createEmpty: {})",
+                window.every, aggregate, window.create_empty
+            ),
+            None => format!("|> {}()", aggregate),
+        }
+    }
+
+    pub fn build(&self) -> Result<String, Box<dyn Error>> {
+        self.validate()?;
+        let mut source = self.source()?;
+
+        // 没有聚合时直接返回原始数据
+        if self.aggregates.is_empty() {
+            self.finish(&mut source);
+            return Ok(source.join("\n    "));
+        }
+
+        let mut query = format!("data = {}\n", source.join("\n    "));
+        for aggregate in &self.aggregates {
+            let mut lines = vec!["data".to_string(), self.aggregate_line(*aggregate)];
+            self.finish(&mut lines);
+            lines.push(format!(
+                "|> yield(name: {})",
+                escape_string(aggregate.as_str())
+            ));
+            query.push('\n');
+            query.push_str(&lines.join("\n    "));
+            query.push('\n');
+        }
+        Ok(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: i64 = 1627848123;
+    const STOP: i64 = 1627851723;
+
+    fn query() -> FluxQuery {
+        FluxQuery::new(
+            "my_bucket",
+            TimeRange::Absolute {
+                start: START,
+                stop: STOP,
+            },
+        )
+        .measurement("temperature")
+    }
+
+    #[test]
+    fn test_escape_string() {
+        assert_eq!(escape_string("abc"), r#""abc""#);
+        assert_eq!(
+            escape_string(r#"a") |> drop(columns: ["x"]) //"#),
+            r#""a\") |> drop(columns: [\"x\"]) //""#
+        );
+        assert_eq!(escape_string(r"a\b"), r#""a\\b""#);
+        assert_eq!(escape_string("${token}"), r#""\${token}""#);
+        assert_eq!(escape_string("$1\n"), r#""$1\n""#);
+    }
+
+    #[test]
+    fn test_aggregate_parse() {
+        assert_eq!("mean".parse::<Aggregate>(), Ok(Aggregate::Mean));
+        assert_eq!(" stddev ".parse::<Aggregate>(), Ok(Aggregate::Stddev));
+        assert!("原始".parse::<Aggregate>().is_err());
+        assert!("sum() |> drop".parse::<Aggregate>().is_err());
+        assert!("MEAN".parse::<Aggregate>().is_err());
+    }
+
+    #[test]
+    fn test_raw_query() {
+        let flux = query().field("value").build().unwrap();
+        assert_eq!(
+            flux,
+            [
+                r#"from(bucket: "my_bucket")"#,
+                "    |> range(start: 1627848123, stop: 1627851723)",
+                r#"    |> filter(fn: (r) => r["_measurement"] == "temperature")"#,
+                r#"    |> filter(fn: (r) => r["_field"] == "value")"#,
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_escaped_identifiers() {
+        let flux = FluxQuery::new(
+            r#"b") |> yield() //"#,
+            TimeRange::Relative {
+                start: 60,
+                stop: None,
+            },
+        )
+        .measurement(r#"m" or true or r["x"] == ""#)
+        .field("val\"ue")
+        .build()
+        .unwrap();
+        assert!(flux.contains(r#"from(bucket: "b\") |> yield() //")"#));
+        assert!(flux.contains(r#"r["_measurement"] == "m\" or true or r[\"x\"] == \"")"#));
+        assert!(flux.contains(r#"r["_field"] == "val\"ue")"#));
+    }
+
+    #[test]
+    fn test_time_range() {
+        let relative =
+            |start, stop| FluxQuery::new("b", TimeRange::Relative { start, stop }).build();
+        assert!(relative(3600, None)
+            .unwrap()
+            .contains("|> range(start: -3600s)"));
+        assert!(relative(3600, Some(60))
+            .unwrap()
+            .contains("|> range(start: -3600s, stop: -60s)"));
+        assert!(relative(60, Some(3600)).is_err());
+        assert!(relative(0, None).is_err());
+
+        let absolute = FluxQuery::new(
+            "b",
+            TimeRange::Absolute {
+                start: STOP,
+                stop: START,
+            },
+        );
+        assert!(absolute.build().is_err());
+    }
+
+    #[test]
+    fn test_fields_and_tags() {
+        let flux = query()
+            .field("a")
+            .field("b")
+            .tag_in("site", &["s1", "s\"2"])
+            .tag_ne("host", "h1")
+            .build()
+            .unwrap();
+        assert!(flux.contains(r#"|> filter(fn: (r) => r["_field"] == "a" or r["_field"] == "b")"#));
+        assert!(flux.contains(r#"|> filter(fn: (r) => r["site"] == "s1" or r["site"] == "s\"2")"#));
+        assert!(flux.contains(r#"|> filter(fn: (r) => r["host"] != "h1")"#));
+
+        assert!(query().tag_in("site", &[]).build().is_err());
+        assert!(query().tag_eq("", "v").build().is_err());
+    }
+
+    #[test]
+    fn test_window_aggregate() {
+        let flux = query()
+            .field("value")
+            .window(60, true)
+            .aggregate(Aggregate::Mean)
+            .build()
+            .unwrap();
+        assert!(flux.starts_with(r#"data = from(bucket: "my_bucket")"#));
+        assert!(flux.contains("|> aggregateWindow(every: 60s, fn: mean, createEmpty: true)"));
+        assert!(flux.contains(r#"|> yield(name: "mean")"#));
+
+        assert!(query()
+            .window(0, false)
+            .aggregate(Aggregate::Mean)
+            .build()
+            .is_err());
+        assert!(query().window(60, false).build().is_err());
+    }
+
+    #[test]
+    fn test_reduce() {
+        let flux = query()
+            .field("value")
+            .aggregate(Aggregate::Sum)
+            .build()
+            .unwrap();
+        assert!(flux.contains("data\n    |> sum()\n    |> yield(name: \"sum\")"));
+        assert!(!flux.contains("aggregateWindow"));
+    }
+
+    #[test]
+    fn test_multiple_aggregates() {
+        let flux = query()
+            .window(300, false)
+            .aggregate(Aggregate::Min)
+            .aggregate(Aggregate::Max)
+            .build()
+            .unwrap();
+        assert!(flux.contains(
+            "data\n    |> aggregateWindow(every: 300s, fn: min, createEmpty: false)\n    |> yield(name: \"min\")"
+        ));
+        assert!(flux.contains(
+            "data\n    |> aggregateWindow(every: 300s, fn: max, createEmpty: false)\n    |> yield(name: \"max\")"
+        ));
+
+        let duplicated = query().aggregate(Aggregate::Max).aggregate(Aggregate::Max);
+        assert!(duplicated.build().is_err());
+    }
+
+    #[test]
+    fn test_group_by() {
+        let flux = query()
+            .group_by("site")
+            .group_by("host")
+            .aggregate(Aggregate::Count)
+            .build()
+            .unwrap();
+        assert!(flux.contains(r#"|> group(columns: ["site", "host"])"#));
+        // 分组在聚合之前
+        assert!(flux.find("group(").unwrap() < flux.find("count()").unwrap());
+    }
+
+    #[test]
+    fn test_fill() {
+        let windowed = || query().window(60, true).aggregate(Aggregate::Mean);
+        assert!(windowed()
+            .fill(Fill::Previous)
+            .build()
+            .unwrap()
+            .contains("|> fill(usePrevious: true)"));
+        assert!(windowed()
+            .fill(Fill::Value(0.0))
+            .build()
+            .unwrap()
+            .contains("|> fill(value: 0.0)"));
+        assert!(windowed().fill(Fill::Value(f64::NAN)).build().is_err());
+
+        // 不生成空窗口时没有可填充的数据
+        let no_empty = query().window(60, false).aggregate(Aggregate::Mean);
+        assert!(no_empty.fill(Fill::Previous).build().is_err());
+        assert!(query().fill(Fill::Previous).build().is_err());
+    }
+
+    #[test]
+    fn test_pivot() {
+        let flux = query().field("a").field("b").pivot().build().unwrap();
+        assert!(flux.ends_with(
+            r#"|> pivot(rowKey: ["_time"], columnKey: ["_field"], valueColumn: "_value")"#
+        ));
+    }
+
+    #[test]
+    fn test_limit() {
+        let flux = query().limit(10, 20).build().unwrap();
+        assert!(flux.ends_with("|> limit(n: 10, offset: 20)"));
+
+        let flux = query()
+            .window(60, true)
+            .aggregate(Aggregate::Last)
+            .fill(Fill::Previous)
+            .pivot()
+            .limit(5, 0)
+            .build()
+            .unwrap();
+        let fill = flux.find("fill(").unwrap();
+        let pivot = flux.find("pivot(").unwrap();
+        let limit = flux.find("limit(").unwrap();
+        let yield_ = flux.find("yield(").unwrap();
+        assert!(fill < pivot && pivot < limit && limit < yield_);
+
+        assert!(query().limit(0, 0).build().is_err());
+    }
+}
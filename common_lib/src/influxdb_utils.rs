@@ -5,10 +5,41 @@ use influxdb2::Client;
 use std::collections::HashMap;
 use std::error::Error;
 
+use crate::config::{InfluxConfig, InfluxWriterConfig};
+use crate::flux_query::{FluxQuery, TimeRange};
 use crate::models::DataValue;
 use futures::prelude::*;
 use influxdb2::api::query::FluxRecord;
-use log::info;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, Mutex, OnceCell};
+
+/// 落盘文件名, 每行为一批数据点的 JSON
+const SPILL_FILE: &str = "influx_spill.jsonl";
+
+/// 正在重新写入的落盘文件及其已写入的字节位置
+const REPLAY_FILE: &str = "influx_spill.replay.jsonl";
+const REPLAY_OFFSET_FILE: &str = "influx_spill.replay.offset";
+
+/// 每个写入周期最多重新写入的块数, 避免长时间阻塞写入循环
+const REPLAY_CHUNKS: usize = 10;
+
+static INFLUX_WRITER: OnceCell<InfluxWriter> = OnceCell::const_new();
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InfluxPoint {
+    pub measurement: String,
+    pub tags: HashMap<String, String>,
+    pub fields: HashMap<String, DataValue>,
+    pub timestamp: Option<i64>, // 纳秒时间戳, 为空时使用服务端时间
+}
 
 pub struct InfluxDBManager {
     client: Client,
@@ -60,25 +91,62 @@ impl InfluxDBManager {
         measurement: &str,
         bucket: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut point = DataPoint::builder(measurement);
-        for (key, value) in kv {
-            match value {
-                DataValue::Integer(v) => {
-                    point = point.field(key, v);
-                }
-                DataValue::Float(v) => {
-                    point = point.field(key, v);
+        let point = InfluxPoint {
+            measurement: measurement.to_string(),
+            tags: HashMap::new(),
+            fields: kv,
+            timestamp: None,
+        };
+        self.write_points(vec![point], bucket).await
+    }
+
+    /// 批量写入, 一次请求写入多个数据点
+    pub async fn write_points(
+        &self,
+        points: Vec<InfluxPoint>,
+        bucket: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut data_points = Vec::with_capacity(points.len());
+        for p in points {
+            // 没有字段的点会被 InfluxDB 拒绝, 导致整批写入失败
+            if p.fields.is_empty() {
+                debug!("skip point without fields: {}", p.measurement);
+                continue;
+            }
+            let mut point = DataPoint::builder(p.measurement);
+            for (key, value) in p.tags {
+                // tag 值不能为空
+                if value.is_empty() {
+                    continue;
                 }
-                DataValue::Text(v) => {
-                    point = point.field(key, v);
+                point = point.tag(key, value);
+            }
+            for (key, value) in p.fields {
+                match value {
+                    DataValue::Integer(v) => {
+                        point = point.field(key, v);
+                    }
+                    DataValue::Float(v) => {
+                        point = point.field(key, v);
+                    }
+                    DataValue::Text(v) => {
+                        point = point.field(key, v);
+                    }
+                    DataValue::Bool(v) => {
+                        point = point.field(key, v);
+                    }
                 }
             }
+            if let Some(timestamp) = p.timestamp {
+                point = point.timestamp(timestamp);
+            }
+            data_points.push(point.build()?);
+        }
+        if data_points.is_empty() {
+            return Ok(());
         }
 
-        let data_point = point.build()?;
-        self.client
-            .write(bucket, stream::iter(vec![data_point]))
-            .await?;
+        self.client.write(bucket, stream::iter(data_points)).await?;
         Ok(())
     }
 
@@ -89,15 +157,15 @@ impl InfluxDBManager {
         stop: DateTime<Utc>,
         bucket: &str,
     ) -> Result<Vec<FluxRecord>, Box<dyn Error>> {
-        let flux_query = format!(
-            "from(bucket: \"{}\")
-            |> range(start: {}, stop: {})
-            |> filter(fn: (r) => r._measurement == \"{}\")",
+        let flux_query = FluxQuery::new(
             bucket,
-            start.timestamp(),
-            stop.timestamp(),
-            measurement
-        );
+            TimeRange::Absolute {
+                start: start.timestamp(),
+                stop: stop.timestamp(),
+            },
+        )
+        .measurement(measurement)
+        .build()?;
 
         let query = Query::new(flux_query);
         let response = self.client.query_raw(Some(query)).await?;
@@ -117,6 +185,401 @@ impl InfluxDBManager {
     }
 }
 
+pub fn init_influx_writer(config: InfluxWriterConfig) -> Result<(), Box<dyn Error>> {
+    INFLUX_WRITER
+        .set(InfluxWriter::new(config))
+        .map_err(|_| "Influx writer is already initialized".into())
+}
+
+pub fn get_influx_writer() -> Result<&'static InfluxWriter, Box<dyn Error>> {
+    Ok(INFLUX_WRITER
+        .get()
+        .ok_or("Influx writer has not been initialized")?)
+}
+
+/// 运行共享写入器的后台写入循环, 需先调用 init_influx_writer
+pub async fn run_influx_writer(config: &InfluxConfig) {
+    let manager = InfluxDBManager::new(
+        config.host.clone().unwrap().as_str(),
+        config.port.unwrap(),
+        config.org.clone().unwrap().as_str(),
+        config.token.clone().unwrap().as_str(),
+    );
+    get_influx_writer()
+        .unwrap()
+        .run_with_manager(&manager)
+        .await;
+}
+
+#[derive(Serialize, Deserialize)]
+struct SpilledBatch {
+    bucket: String,
+    points: Vec<InfluxPoint>,
+}
+
+/// 共享的 InfluxDB 写入器, 按 bucket 缓存数据点后批量写入
+///
+/// 写入失败时按退避时间重试, 重试后仍失败时熔断 max_backoff_ms, 熔断期间的数据直接落盘,
+/// InfluxDB 恢复后重新写入; 内存队列已满时丢弃新数据点
+pub struct InfluxWriter {
+    config: InfluxWriterConfig,
+    sender: mpsc::Sender<(String, InfluxPoint)>,
+    receiver: Mutex<Option<mpsc::Receiver<(String, InfluxPoint)>>>,
+    spill_lock: Mutex<()>,
+    dropped: AtomicU64,
+}
+
+impl InfluxWriter {
+    pub fn new(config: InfluxWriterConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.queue_size.max(1));
+        InfluxWriter {
+            config,
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            spill_lock: Mutex::new(()),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// 加入写入队列, 队列已满时丢弃数据点并计数, 不影响调用方
+    pub fn write(&self, bucket: &str, point: InfluxPoint) -> Result<(), Box<dyn Error>> {
+        match self.sender.try_send((bucket.to_string(), point)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                // 队列持续满时避免每个数据点都打印日志
+                if dropped.is_power_of_two() {
+                    warn!(
+                        "InfluxDB 写入队列已满, bucket = {}, 累计丢弃 {} 个数据点",
+                        bucket, dropped
+                    );
+                }
+                Ok(())
+            }
+            Err(TrySendError::Closed(_)) => Err("InfluxDB 写入队列已关闭".into()),
+        }
+    }
+
+    /// 队列已满时累计丢弃的数据点数量
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub async fn run_with_manager(&self, manager: &InfluxDBManager) {
+        self.run(|bucket: String, points| async move {
+            manager.write_points(points, bucket.as_str()).await
+        })
+        .await
+    }
+
+    /// 后台写入循环, 单个 bucket 缓存达到 batch_size 或到达 flush_interval_ms 时写入
+    pub async fn run<F, Fut>(&self, write: F)
+    where
+        F: Fn(String, Vec<InfluxPoint>) -> Fut,
+        Fut: Future<Output = Result<(), Box<dyn Error>>>,
+    {
+        let mut receiver = match self.receiver.lock().await.take() {
+            Some(receiver) => receiver,
+            None => {
+                error!("InfluxDB 写入器已在运行");
+                return;
+            }
+        };
+        let mut buffers: HashMap<String, Vec<InfluxPoint>> = HashMap::new();
+        let mut interval =
+            tokio::time::interval(Duration::from_millis(self.config.flush_interval_ms.max(1)));
+        interval.tick().await;
+        let mut breaker = Breaker::default();
+
+        info!("influx writer started");
+        loop {
+            tokio::select! {
+                received = receiver.recv() => match received {
+                    Some((bucket, point)) => {
+                        let buffer = buffers.entry(bucket.clone()).or_default();
+                        buffer.push(point);
+                        if buffer.len() >= self.config.batch_size {
+                            let points = std::mem::take(buffer);
+                            self.flush(bucket, points, &write, &mut breaker).await;
+                        }
+                    }
+                    None => break,
+                },
+                _ = interval.tick() => {
+                    for (bucket, points) in buffers.iter_mut() {
+                        if !points.is_empty() {
+                            let points = std::mem::take(points);
+                            self.flush(bucket.clone(), points, &write, &mut breaker).await;
+                        }
+                    }
+                    // 空闲的 bucket 不再保留缓存
+                    buffers.retain(|_, points| points.capacity() > 0);
+                    if breaker.is_closed() {
+                        match self.replay_spill(&write).await {
+                            Ok(true) => {}
+                            Ok(false) => breaker.trip(self.config.max_backoff_ms),
+                            Err(e) => error!("读取 InfluxDB 落盘数据失败: {}", e),
+                        }
+                    }
+                }
+            }
+        }
+
+        // 队列关闭时写入剩余数据
+        for (bucket, points) in buffers.drain() {
+            self.flush(bucket, points, &write, &mut breaker).await;
+        }
+    }
+
+    /// 写入一批数据点, 熔断期间或重试后仍失败时落盘
+    ///
+    /// 熔断到期后的第一次写入不重试, 成功后恢复正常写入
+    async fn flush<F, Fut>(
+        &self,
+        bucket: String,
+        points: Vec<InfluxPoint>,
+        write: &F,
+        breaker: &mut Breaker,
+    ) where
+        F: Fn(String, Vec<InfluxPoint>) -> Fut,
+        Fut: Future<Output = Result<(), Box<dyn Error>>>,
+    {
+        if points.is_empty() {
+            return;
+        }
+        let max_retries = match breaker.state() {
+            BreakerState::Open => {
+                self.spill_or_drop(bucket.as_str(), points).await;
+                return;
+            }
+            BreakerState::HalfOpen => 0,
+            BreakerState::Closed => self.config.max_retries,
+        };
+
+        debug!("influx write {} points to {}", points.len(), bucket);
+        let mut backoff = self.config.retry_backoff_ms;
+        let mut attempt = 0;
+        loop {
+            let result = write(bucket.clone(), points.clone())
+                .await
+                .map_err(|e| e.to_string());
+            match result {
+                Ok(()) => {
+                    breaker.reset();
+                    return;
+                }
+                Err(e) if attempt >= max_retries => {
+                    error!(
+                        "写入 InfluxDB 失败, bucket = {}, {} ms 内不再尝试写入: {}",
+                        bucket, self.config.max_backoff_ms, e
+                    );
+                    break;
+                }
+                Err(e) => {
+                    warn!("写入 InfluxDB 失败, {} ms 后重试: {}", backoff, e);
+                    tokio::time::sleep(Duration::from_millis(backoff)).await;
+                    backoff = (backoff * 2).min(self.config.max_backoff_ms);
+                    attempt += 1;
+                }
+            }
+        }
+        breaker.trip(self.config.max_backoff_ms);
+        self.spill_or_drop(bucket.as_str(), points).await;
+    }
+
+    /// 落盘文件和正在重新写入的文件
+    fn spill_paths(&self) -> Option<(PathBuf, PathBuf)> {
+        self.config.spill_dir.as_ref().map(|dir| {
+            let dir = PathBuf::from(dir);
+            (dir.join(SPILL_FILE), dir.join(REPLAY_FILE))
+        })
+    }
+
+    async fn spill_or_drop(&self, bucket: &str, points: Vec<InfluxPoint>) {
+        if let Err(e) = self.spill(bucket, points).await {
+            error!("{}", e);
+        }
+    }
+
+    async fn spill(&self, bucket: &str, points: Vec<InfluxPoint>) -> Result<(), Box<dyn Error>> {
+        let count = points.len();
+        let (path, replay) = match self.spill_paths() {
+            Some(paths) => paths,
+            None => return Err(format!("未配置落盘目录, 丢弃 {} 个数据点", count).into()),
+        };
+        let mut line = serde_json::to_string(&SpilledBatch {
+            bucket: bucket.to_string(),
+            points,
+        })?;
+        line.push('\n');
+
+        let _guard = self.spill_lock.lock().await;
+        // 计入本次待写入的数据, 避免落盘文件超出上限一整个批次
+        let size = file_size(&path).await + file_size(&replay).await + line.len() as u64;
+        if size > self.config.max_spill_mb * 1024 * 1024 {
+            return Err(format!("落盘文件已达上限, 丢弃 {} 个数据点", count).into());
+        }
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        warn!("{} 个数据点已落盘, bucket = {}", count, bucket);
+        Ok(())
+    }
+
+    /// 重新写入落盘数据, 写入失败时返回 false
+    ///
+    /// 落盘文件先改名为 REPLAY_FILE, 之后的落盘数据写入新文件; REPLAY_FILE 按行读取,
+    /// 同一 bucket 的相邻批次合并为不超过 batch_size 的块写入, 每轮最多写入 REPLAY_CHUNKS 块,
+    /// 已写入的位置记录在 REPLAY_OFFSET_FILE 中
+    async fn replay_spill<F, Fut>(&self, write: &F) -> Result<bool, Box<dyn Error>>
+    where
+        F: Fn(String, Vec<InfluxPoint>) -> Fut,
+        Fut: Future<Output = Result<(), Box<dyn Error>>>,
+    {
+        let (path, replay) = match self.spill_paths() {
+            Some(paths) => paths,
+            None => return Ok(true),
+        };
+        let offset_path = replay.with_file_name(REPLAY_OFFSET_FILE);
+        if tokio::fs::metadata(&replay).await.is_err() {
+            let _guard = self.spill_lock.lock().await;
+            match tokio::fs::rename(&path, &replay).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(true),
+                Err(e) => return Err(e.into()),
+            }
+            let _ = tokio::fs::remove_file(&offset_path).await;
+        }
+
+        let mut done: u64 = match tokio::fs::read_to_string(&offset_path).await {
+            Ok(offset) => offset.trim().parse().unwrap_or(0),
+            Err(_) => 0,
+        };
+        let mut file = tokio::fs::File::open(&replay).await?;
+        file.seek(SeekFrom::Start(done)).await?;
+        let mut lines = BufReader::new(file).lines();
+
+        let mut pos = done;
+        let mut chunks = 0;
+        let mut pending: Option<(String, Vec<InfluxPoint>)> = None;
+        let mut ok = true;
+        let mut eof = false;
+        loop {
+            let line = lines.next_line().await?;
+            let batch = match &line {
+                Some(line) => match serde_json::from_str::<SpilledBatch>(line) {
+                    Ok(batch) => Some(batch),
+                    Err(e) => {
+                        error!("落盘数据无法解析, 已丢弃: {}", e);
+                        None
+                    }
+                },
+                None => None,
+            };
+
+            // 遇到不同的 bucket、块已满或读到文件末尾时写入当前块
+            let flush = match (&pending, &batch) {
+                (Some(_), None) => line.is_none(),
+                (Some((bucket, points)), Some(batch)) => {
+                    *bucket != batch.bucket
+                        || points.len() + batch.points.len() > self.config.batch_size
+                }
+                (None, _) => false,
+            };
+            if flush {
+                let (bucket, points) = pending.take().unwrap();
+                let count = points.len();
+                if let Err(e) = write(bucket, points).await.map_err(|e| e.to_string()) {
+                    warn!("重新写入落盘数据失败: {}", e);
+                    ok = false;
+                    break;
+                }
+                info!("重新写入 {} 个落盘数据点", count);
+                done = pos;
+                chunks += 1;
+                if chunks >= REPLAY_CHUNKS {
+                    break;
+                }
+            }
+
+            let line = match line {
+                Some(line) => line,
+                None => {
+                    eof = true;
+                    break;
+                }
+            };
+            pos += line.len() as u64 + 1;
+            if let Some(batch) = batch {
+                match &mut pending {
+                    Some((_, points)) => points.extend(batch.points),
+                    None => pending = Some((batch.bucket, batch.points)),
+                }
+            } else if pending.is_none() {
+                // 无法解析的行不会再次读取
+                done = pos;
+            }
+        }
+
+        if ok && eof {
+            tokio::fs::remove_file(&replay).await?;
+            let _ = tokio::fs::remove_file(&offset_path).await;
+            return Ok(true);
+        }
+        tokio::fs::write(&offset_path, done.to_string()).await?;
+        Ok(ok)
+    }
+}
+
+async fn file_size(path: &Path) -> u64 {
+    tokio::fs::metadata(path)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+/// 写入熔断, 写入失败后在一段时间内不再尝试写入
+#[derive(Debug, Default)]
+struct Breaker {
+    open_until: Option<Instant>,
+}
+
+impl Breaker {
+    fn state(&self) -> BreakerState {
+        match self.open_until {
+            None => BreakerState::Closed,
+            Some(until) if Instant::now() < until => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state() == BreakerState::Closed
+    }
+
+    fn trip(&mut self, millis: u64) {
+        self.open_until = Some(Instant::now() + Duration::from_millis(millis));
+    }
+
+    fn reset(&mut self) {
+        self.open_until = None;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -186,4 +649,208 @@ mod tests {
 
         Ok(())
     }
+
+    fn test_writer(spill_dir: Option<String>) -> InfluxWriter {
+        InfluxWriter::new(InfluxWriterConfig {
+            batch_size: 2,
+            flush_interval_ms: 10_000,
+            queue_size: 1,
+            max_retries: 2,
+            retry_backoff_ms: 1,
+            max_backoff_ms: 2,
+            spill_dir,
+            ..Default::default()
+        })
+    }
+
+    fn test_point(value: f64) -> InfluxPoint {
+        let mut fields = HashMap::new();
+        fields.insert("value".to_string(), DataValue::Float(value));
+        InfluxPoint {
+            measurement: "m".to_string(),
+            tags: HashMap::new(),
+            fields,
+            timestamp: Some(1730808000 * 1_000_000_000),
+        }
+    }
+
+    fn spill_dir(name: &str) -> String {
+        let dir = env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_writer_batch_size() {
+        let writer = test_writer(None);
+        let written = std::sync::Mutex::new(Vec::new());
+        let write = |bucket: String, points: Vec<InfluxPoint>| {
+            written.lock().unwrap().push((bucket, points.len()));
+            async { Ok::<(), Box<dyn Error>>(()) }
+        };
+
+        let sender = writer.sender.clone();
+        let feed = async {
+            for i in 0..3 {
+                sender
+                    .send(("a".to_string(), test_point(i as f64)))
+                    .await
+                    .unwrap();
+            }
+        };
+        let run = tokio::time::timeout(std::time::Duration::from_millis(50), writer.run(write));
+        let _ = tokio::join!(feed, run);
+
+        // 第三个点未达到 batch_size, 等待下一个写入周期
+        assert_eq!(*written.lock().unwrap(), vec![("a".to_string(), 2)]);
+    }
+
+    fn values(points: &[InfluxPoint]) -> Vec<f64> {
+        points
+            .iter()
+            .map(|p| match p.fields["value"] {
+                DataValue::Float(v) => v,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_writer_breaker() {
+        let dir = spill_dir("influx_writer_breaker");
+        let mut writer = test_writer(Some(dir.clone()));
+        writer.config.max_backoff_ms = 50;
+        let attempts = std::sync::Mutex::new(0);
+        let failing = |_: String, _: Vec<InfluxPoint>| {
+            *attempts.lock().unwrap() += 1;
+            async { Err::<(), Box<dyn Error>>("influxdb unavailable".into()) }
+        };
+        let mut breaker = Breaker::default();
+
+        let points = vec![test_point(1.0), test_point(2.0)];
+        writer
+            .flush("a".to_string(), points.clone(), &failing, &mut breaker)
+            .await;
+        assert_eq!(*attempts.lock().unwrap(), 3);
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        // 熔断期间直接落盘, 不再尝试写入
+        writer
+            .flush("b".to_string(), points.clone(), &failing, &mut breaker)
+            .await;
+        assert_eq!(*attempts.lock().unwrap(), 3);
+
+        // 熔断到期后只尝试一次
+        tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        writer
+            .flush("c".to_string(), points.clone(), &failing, &mut breaker)
+            .await;
+        assert_eq!(*attempts.lock().unwrap(), 4);
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        assert!(!writer.replay_spill(&failing).await.unwrap());
+
+        let written = std::sync::Mutex::new(Vec::new());
+        let ok = |bucket: String, points: Vec<InfluxPoint>| {
+            written.lock().unwrap().push((bucket, points.len()));
+            async { Ok::<(), Box<dyn Error>>(()) }
+        };
+        assert!(writer.replay_spill(&ok).await.unwrap());
+        assert_eq!(
+            *written.lock().unwrap(),
+            vec![
+                ("a".to_string(), 2),
+                ("b".to_string(), 2),
+                ("c".to_string(), 2)
+            ]
+        );
+        let (path, replay) = writer.spill_paths().unwrap();
+        assert!(!path.exists() && !replay.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_writer_replay_resume() {
+        let dir = spill_dir("influx_writer_replay");
+        let writer = test_writer(Some(dir.clone()));
+        for i in 0..5 {
+            writer.spill("a", vec![test_point(i as f64)]).await.unwrap();
+        }
+
+        let written = std::sync::Mutex::new(Vec::new());
+        let fail_at = std::sync::Mutex::new(Some(1));
+        let write = |bucket: String, points: Vec<InfluxPoint>| {
+            let mut written = written.lock().unwrap();
+            let fail = *fail_at.lock().unwrap() == Some(written.len());
+            if !fail {
+                written.push((bucket, values(&points)));
+            }
+            async move {
+                if fail {
+                    Err::<(), Box<dyn Error>>("influxdb unavailable".into())
+                } else {
+                    Ok(())
+                }
+            }
+        };
+
+        // 第二块写入失败, 下次从第二块继续
+        assert!(!writer.replay_spill(&write).await.unwrap());
+        *fail_at.lock().unwrap() = None;
+        // 重新写入期间的落盘数据写入新文件, 下一轮处理
+        writer.spill("b", vec![test_point(9.0)]).await.unwrap();
+
+        assert!(writer.replay_spill(&write).await.unwrap());
+        assert!(writer.replay_spill(&write).await.unwrap());
+        assert_eq!(
+            *written.lock().unwrap(),
+            vec![
+                ("a".to_string(), vec![0.0, 1.0]),
+                ("a".to_string(), vec![2.0, 3.0]),
+                ("a".to_string(), vec![4.0]),
+                ("b".to_string(), vec![9.0]),
+            ]
+        );
+        let (path, replay) = writer.spill_paths().unwrap();
+        assert!(!path.exists() && !replay.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_writer_replay_chunks() {
+        let dir = spill_dir("influx_writer_chunks");
+        let writer = test_writer(Some(dir.clone()));
+        for i in 0..(REPLAY_CHUNKS * 2 + 1) {
+            writer.spill("a", vec![test_point(i as f64)]).await.unwrap();
+        }
+
+        let chunks = std::sync::Mutex::new(0);
+        let ok = |_: String, _: Vec<InfluxPoint>| {
+            *chunks.lock().unwrap() += 1;
+            async { Ok::<(), Box<dyn Error>>(()) }
+        };
+        // 每轮最多写入 REPLAY_CHUNKS 块
+        assert!(writer.replay_spill(&ok).await.unwrap());
+        assert_eq!(*chunks.lock().unwrap(), REPLAY_CHUNKS);
+        assert!(writer.spill_paths().unwrap().1.exists());
+
+        assert!(writer.replay_spill(&ok).await.unwrap());
+        assert_eq!(*chunks.lock().unwrap(), REPLAY_CHUNKS + 1);
+        assert!(!writer.spill_paths().unwrap().1.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_writer_queue_full() {
+        let writer = test_writer(None);
+        writer.write("a", test_point(1.0)).unwrap();
+        // 队列已满时丢弃, 不返回错误
+        writer.write("a", test_point(2.0)).unwrap();
+        writer.write("b", test_point(3.0)).unwrap();
+        assert_eq!(writer.dropped(), 2);
+    }
 }
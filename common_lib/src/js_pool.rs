@@ -0,0 +1,281 @@
+use crate::js_sandbox::{JsSandbox, ScriptError, ScriptLimits};
+use log::debug;
+use once_cell::sync::Lazy;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// 进程内缓存的脚本数量上限
+const DEFAULT_CAPACITY: usize = 256;
+
+static SCRIPT_POOL: Lazy<ScriptPool> = Lazy::new(|| ScriptPool::new(DEFAULT_CAPACITY));
+
+pub fn script_hash(script: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    script.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 脚本执行过程中 panic 不影响缓存结构, 忽略锁中毒继续使用
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+struct PooledSandbox {
+    hash: u64,
+    sandbox: Arc<Mutex<JsSandbox>>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct PoolState {
+    sandboxes: HashMap<String, PooledSandbox>,
+    tick: u64,
+}
+
+/// 已加载脚本的沙箱缓存, 热路径只调用 main
+///
+/// 按 owner 缓存, owner 为脚本在 Redis 中的位置 (例如 mqtt_script:{client_id}),
+/// 内容相同的脚本也不共用沙箱, 全局变量只在同一 owner 的多次调用间保留.
+/// owner 的脚本内容变化时重新加载. 每个沙箱单独加锁, 不同 owner 可以并行执行
+pub struct ScriptPool {
+    capacity: usize,
+    limits: ScriptLimits,
+    state: Mutex<PoolState>,
+}
+
+impl ScriptPool {
+    pub fn new(capacity: usize) -> Self {
+        ScriptPool {
+            capacity: capacity.max(1),
+            limits: ScriptLimits::default(),
+            state: Mutex::new(PoolState::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        lock(&self.state).sandboxes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        lock(&self.state).sandboxes.is_empty()
+    }
+
+    /// 调用脚本函数, parse 为 true 时参数按 JSON 解析后传入
+    pub fn call(
+        &self,
+        owner: &str,
+        script: &str,
+        function: &str,
+        input: &str,
+        parse: bool,
+    ) -> Result<String, ScriptError> {
+        let hash = script_hash(script);
+        let cached = lock(&self.state).touch(owner, hash);
+        let sandbox = match cached {
+            Some(sandbox) => sandbox,
+            None => {
+                // 加载脚本可能耗时较长, 不持有缓存锁
+                let sandbox = JsSandbox::new(self.limits)?;
+                sandbox.load(script)?;
+                debug!("script {} loaded for {}", hash, owner);
+                lock(&self.state).insert(owner, hash, sandbox, self.capacity)
+            }
+        };
+
+        let result = {
+            let sandbox = lock(&sandbox);
+            if parse {
+                sandbox.call_json(function, input)
+            } else {
+                sandbox.call(function, input)
+            }
+        };
+
+        // 超时或超出资源限制后运行时状态不可信, 下次重新加载
+        if let Err(
+            ScriptError::Timeout(_) | ScriptError::OutOfMemory | ScriptError::StackOverflow,
+        ) = result
+        {
+            let mut state = lock(&self.state);
+            if let Some(pooled) = state.sandboxes.get(owner) {
+                if Arc::ptr_eq(&pooled.sandbox, &sandbox) {
+                    state.sandboxes.remove(owner);
+                }
+            }
+        }
+        result
+    }
+
+    /// 移除 owner 的沙箱
+    pub fn invalidate(&self, owner: &str) {
+        lock(&self.state).sandboxes.remove(owner);
+    }
+}
+
+impl PoolState {
+    /// 取出 owner 当前脚本的沙箱, 脚本内容变化时淘汰旧沙箱
+    fn touch(&mut self, owner: &str, hash: u64) -> Option<Arc<Mutex<JsSandbox>>> {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(pooled) = self.sandboxes.get_mut(owner) {
+            if pooled.hash == hash {
+                pooled.last_used = tick;
+                return Some(pooled.sandbox.clone());
+            }
+        }
+        if self.sandboxes.remove(owner).is_some() {
+            debug!("script of {} changed", owner);
+        }
+        None
+    }
+
+    /// 缓存新加载的沙箱, 其他线程已加载同一脚本时使用已缓存的沙箱
+    fn insert(
+        &mut self,
+        owner: &str,
+        hash: u64,
+        sandbox: JsSandbox,
+        capacity: usize,
+    ) -> Arc<Mutex<JsSandbox>> {
+        if let Some(pooled) = self.sandboxes.get(owner) {
+            if pooled.hash == hash {
+                return pooled.sandbox.clone();
+            }
+        }
+        self.sandboxes.remove(owner);
+        self.evict(capacity);
+        self.tick += 1;
+        let sandbox = Arc::new(Mutex::new(sandbox));
+        self.sandboxes.insert(
+            owner.to_string(),
+            PooledSandbox {
+                hash,
+                sandbox: sandbox.clone(),
+                last_used: self.tick,
+            },
+        );
+        sandbox
+    }
+
+    /// 超出容量时淘汰最久未使用的沙箱
+    fn evict(&mut self, capacity: usize) {
+        while self.sandboxes.len() >= capacity {
+            let oldest = self
+                .sandboxes
+                .iter()
+                .min_by_key(|(_, pooled)| pooled.last_used)
+                .map(|(owner, _)| owner.clone());
+            match oldest {
+                Some(owner) => {
+                    self.sandboxes.remove(&owner);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// 使用进程内的脚本缓存调用函数, 参数按字符串传入
+pub fn call_cached(
+    owner: &str,
+    script: &str,
+    function: &str,
+    input: &str,
+) -> Result<String, ScriptError> {
+    SCRIPT_POOL.call(owner, script, function, input, false)
+}
+
+/// 使用进程内的脚本缓存调用函数, 参数为 JSON 字符串
+pub fn call_cached_json(
+    owner: &str,
+    script: &str,
+    function: &str,
+    input: &str,
+) -> Result<String, ScriptError> {
+    SCRIPT_POOL.call(owner, script, function, input, true)
+}
+
+/// 脚本在 Redis 中被删除或修改时移除对应沙箱
+pub fn invalidate_cached(owner: &str) {
+    SCRIPT_POOL.invalidate(owner);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 全局计数器用于判断沙箱是否被复用
+    const COUNTER: &str = "var n = 0; function main() { n += 1; return n; }";
+
+    #[test]
+    fn test_pool_reuse() {
+        let pool = ScriptPool::new(4);
+        assert_eq!(pool.call("a", COUNTER, "main", "", false).unwrap(), "1");
+        assert_eq!(pool.call("a", COUNTER, "main", "", false).unwrap(), "2");
+        // 相同脚本内容的不同 owner 互不影响
+        assert_eq!(pool.call("b", COUNTER, "main", "", false).unwrap(), "1");
+        assert_eq!(pool.call("a", COUNTER, "main", "", false).unwrap(), "3");
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_pool_invalidate_on_change() {
+        let pool = ScriptPool::new(4);
+        pool.call("a", COUNTER, "main", "", false).unwrap();
+
+        let changed = "var n = 10; function main() { n += 1; return n; }";
+        assert_eq!(pool.call("a", changed, "main", "", false).unwrap(), "11");
+        assert_eq!(pool.len(), 1);
+
+        pool.call("b", changed, "main", "", false).unwrap();
+        assert_eq!(pool.len(), 2);
+
+        pool.invalidate("a");
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.call("b", changed, "main", "", false).unwrap(), "12");
+        pool.invalidate("b");
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_pool_evict_and_error() {
+        let pool = ScriptPool::new(2);
+
+        // 语法错误不缓存, 栈溢出后丢弃沙箱
+        assert!(matches!(
+            pool.call("e", "function main( {", "main", "", false),
+            Err(ScriptError::Syntax(_))
+        ));
+        assert_eq!(
+            pool.call("e", "function main() { return main(); }", "main", "", false),
+            Err(ScriptError::StackOverflow)
+        );
+        assert!(pool.is_empty());
+
+        for i in 0..3 {
+            let script = format!("function main() {{ return {}; }}", i);
+            pool.call(format!("s{}", i).as_str(), &script, "main", "", false)
+                .unwrap();
+        }
+        assert_eq!(pool.len(), 2);
+        assert!(!lock(&pool.state).sandboxes.contains_key("s0"));
+    }
+
+    #[test]
+    fn test_pool_shared_across_threads() {
+        let pool = Arc::new(ScriptPool::new(4));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || pool.call("a", COUNTER, "main", "", false).unwrap())
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(pool.call("a", COUNTER, "main", "", false).unwrap(), "5");
+        assert_eq!(pool.len(), 1);
+    }
+}
@@ -0,0 +1,375 @@
+use log::debug;
+use rquickjs::{CatchResultExt, CaughtError, Context, Function, Runtime};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// 用户脚本执行限制
+#[derive(Debug, Clone, Copy)]
+pub struct ScriptLimits {
+    pub timeout_ms: u64,       // 单次加载或调用的最长执行时间
+    pub memory_limit: usize,   // 运行时内存上限(字节)
+    pub max_stack_size: usize, // 调用栈上限(字节)
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        ScriptLimits {
+            timeout_ms: 1000,
+            memory_limit: 32 * 1024 * 1024,
+            max_stack_size: 256 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptError {
+    Init(String),    // 运行时创建失败
+    Syntax(String),  // 脚本语法错误
+    Runtime(String), // 脚本执行抛出异常
+    Timeout(u64),    // 执行超时(毫秒)
+    OutOfMemory,     // 超出内存上限
+    StackOverflow,   // 超出调用栈上限
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Init(e) => write!(f, "脚本运行时创建失败: {}", e),
+            ScriptError::Syntax(e) => write!(f, "脚本语法错误: {}", e),
+            ScriptError::Runtime(e) => write!(f, "脚本执行错误: {}", e),
+            ScriptError::Timeout(ms) => write!(f, "脚本执行超时 ({}ms)", ms),
+            ScriptError::OutOfMemory => write!(f, "脚本超出内存限制"),
+            ScriptError::StackOverflow => write!(f, "脚本超出调用栈限制"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// 调用入口, 结果统一序列化为 JSON 字符串, 与原 main2/main3 的处理方式一致
+const CALL_JS: &str = r#"
+    function __sandbox_call(name, data, parse) {
+        var f = globalThis[name];
+        if (typeof f !== "function") {
+            throw new TypeError(name + " is not a function");
+        }
+        return JSON.stringify(f(parse ? JSON.parse(data) : data));
+    }"#;
+
+/// 默认的 console 丢弃所有输出, 与试运行保持同样的全局对象
+const NOOP_CONSOLE_JS: &str = r#"
+    var console = (function () {
+        function noop() {}
+        return { log: noop, info: noop, warn: noop, error: noop, debug: noop };
+    })();"#;
+
+/// 收集 console 输出, 仅用于脚本调试
+const CONSOLE_JS: &str = r#"
+    var __console = [];
+    function __console_writer(level) {
+        return function () {
+            var args = Array.prototype.map.call(arguments, function (a) {
+                return typeof a === "string" ? a : JSON.stringify(a);
+            });
+            __console.push("[" + level + "] " + args.join(" "));
+        };
+    }
+    var console = {
+        log: __console_writer("log"),
+        info: __console_writer("info"),
+        warn: __console_writer("warn"),
+        error: __console_writer("error"),
+        debug: __console_writer("debug"),
+    };
+    function __console_take() {
+        return __console.splice(0);
+    }"#;
+
+/// 未设置截止时间
+const NO_DEADLINE: u64 = u64::MAX;
+
+/// 独立的 JS 运行时, 加载用户脚本后可多次调用其中的函数
+///
+/// 每次加载和调用都受 ScriptLimits 限制, 超时通过中断回调终止死循环
+pub struct JsSandbox {
+    context: Context,
+    _runtime: Runtime,
+    limits: ScriptLimits,
+    started: Instant,
+    deadline: Arc<AtomicU64>, // 相对 started 的毫秒数
+    interrupted: Arc<AtomicBool>,
+}
+
+impl JsSandbox {
+    pub fn new(limits: ScriptLimits) -> Result<Self, ScriptError> {
+        let runtime = Runtime::new().map_err(|e| ScriptError::Init(e.to_string()))?;
+        runtime.set_memory_limit(limits.memory_limit);
+        runtime.set_max_stack_size(limits.max_stack_size);
+
+        let started = Instant::now();
+        let deadline = Arc::new(AtomicU64::new(NO_DEADLINE));
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let (d, i) = (deadline.clone(), interrupted.clone());
+        runtime.set_interrupt_handler(Some(Box::new(move || {
+            let expired = started.elapsed().as_millis() as u64 >= d.load(Ordering::Relaxed);
+            if expired {
+                i.store(true, Ordering::Relaxed);
+            }
+            expired
+        })));
+
+        let context = Context::full(&runtime).map_err(|e| ScriptError::Init(e.to_string()))?;
+        let sandbox = JsSandbox {
+            context,
+            _runtime: runtime,
+            limits,
+            started,
+            deadline,
+            interrupted,
+        };
+        sandbox.load(CALL_JS)?;
+        sandbox.load(NOOP_CONSOLE_JS)?;
+        Ok(sandbox)
+    }
+
+    /// 加载脚本, 定义其中的函数
+    pub fn load(&self, script: &str) -> Result<(), ScriptError> {
+        self.guard(|| {
+            self.context.with(|ctx| {
+                ctx.eval::<(), _>(script)
+                    .catch(&ctx)
+                    .map_err(|e| self.script_error(e, true))
+            })
+        })
+    }
+
+    /// 开启 console 输出收集, 需要在加载用户脚本前调用
+    pub fn capture_console(&self) -> Result<(), ScriptError> {
+        self.load(CONSOLE_JS)
+    }
+
+    /// 取出已收集的 console 输出
+    pub fn take_console(&self) -> Result<Vec<String>, ScriptError> {
+        let output = self.call("__console_take", "")?;
+        serde_json::from_str(output.as_str()).map_err(|e| ScriptError::Runtime(e.to_string()))
+    }
+
+    /// 调用脚本函数, 参数按字符串原样传入, 返回结果的 JSON 字符串
+    pub fn call(&self, function: &str, input: &str) -> Result<String, ScriptError> {
+        self.call_function(function, input, false)
+    }
+
+    /// 调用脚本函数, 参数为 JSON 字符串, 解析为对象后传入
+    pub fn call_json(&self, function: &str, input: &str) -> Result<String, ScriptError> {
+        self.call_function(function, input, true)
+    }
+
+    fn call_function(
+        &self,
+        function: &str,
+        input: &str,
+        parse: bool,
+    ) -> Result<String, ScriptError> {
+        self.guard(|| {
+            self.context.with(|ctx| {
+                let result: Option<String> = ctx
+                    .globals()
+                    .get::<_, Function>("__sandbox_call")
+                    .and_then(|f| f.call::<_, Option<String>>((function, input, parse)))
+                    .catch(&ctx)
+                    .map_err(|e| self.script_error(e, false))?;
+                // 函数返回 undefined 时 JSON.stringify 结果为 undefined
+                Ok(result.unwrap_or_else(|| "null".to_string()))
+            })
+        })
+    }
+
+    /// 在截止时间内执行
+    fn guard<T>(&self, f: impl FnOnce() -> Result<T, ScriptError>) -> Result<T, ScriptError> {
+        let deadline = self.started.elapsed().as_millis() as u64 + self.limits.timeout_ms;
+        self.interrupted.store(false, Ordering::Relaxed);
+        self.deadline.store(deadline, Ordering::Relaxed);
+        let result = f();
+        self.deadline.store(NO_DEADLINE, Ordering::Relaxed);
+        result
+    }
+
+    fn script_error(&self, err: CaughtError, loading: bool) -> ScriptError {
+        if self.interrupted.load(Ordering::Relaxed) {
+            return ScriptError::Timeout(self.limits.timeout_ms);
+        }
+        match err {
+            CaughtError::Error(rquickjs::Error::Allocation) => ScriptError::OutOfMemory,
+            CaughtError::Error(e) => ScriptError::Runtime(e.to_string()),
+            CaughtError::Exception(e) => {
+                let name: String = e.as_object().get("name").unwrap_or_default();
+                let message = e.message().unwrap_or_default();
+                debug!("script exception {}: {}", name, message);
+                classify_exception(name.as_str(), message.as_str(), loading)
+            }
+            CaughtError::Value(v) => ScriptError::Runtime(format!("{:?}", v)),
+        }
+    }
+}
+
+/// 按异常类型分类, 只有加载阶段的 SyntaxError 视为语法错误 (JSON.parse 失败属于执行错误)
+pub fn classify_exception(name: &str, message: &str, loading: bool) -> ScriptError {
+    if message.contains("out of memory") {
+        ScriptError::OutOfMemory
+    } else if message.contains("stack overflow") || message.contains("call stack") {
+        ScriptError::StackOverflow
+    } else if loading && name == "SyntaxError" {
+        ScriptError::Syntax(message.to_string())
+    } else if name.is_empty() {
+        ScriptError::Runtime(message.to_string())
+    } else {
+        ScriptError::Runtime(format!("{}: {}", name, message))
+    }
+}
+
+/// 使用默认限制执行一次脚本函数, 参数按字符串传入
+pub fn call_script(script: &str, function: &str, input: &str) -> Result<String, ScriptError> {
+    let sandbox = JsSandbox::new(ScriptLimits::default())?;
+    sandbox.load(script)?;
+    sandbox.call(function, input)
+}
+
+/// 使用默认限制执行一次脚本函数, 参数为 JSON 字符串
+pub fn call_script_json(script: &str, function: &str, input: &str) -> Result<String, ScriptError> {
+    let sandbox = JsSandbox::new(ScriptLimits::default())?;
+    sandbox.load(script)?;
+    sandbox.call_json(function, input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(timeout_ms: u64) -> ScriptLimits {
+        ScriptLimits {
+            timeout_ms,
+            ..ScriptLimits::default()
+        }
+    }
+
+    #[test]
+    fn test_call() {
+        let script = r#"
+            function main(nc) {
+                return [{ "DeviceUid": "1", "Nc": nc }];
+            }
+            function check(param) {
+                return param.a.length > 0;
+            }
+            function nothing() {}
+        "#;
+        assert_eq!(
+            call_script(script, "main", "42").unwrap(),
+            r#"[{"DeviceUid":"1","Nc":"42"}]"#
+        );
+        assert_eq!(
+            call_script_json(script, "check", r#"{"a":[1]}"#).unwrap(),
+            "true"
+        );
+        assert_eq!(call_script(script, "nothing", "").unwrap(), "null");
+    }
+
+    #[test]
+    fn test_syntax_and_runtime_error() {
+        assert!(matches!(
+            call_script("function main( {", "main", ""),
+            Err(ScriptError::Syntax(_))
+        ));
+        assert!(matches!(
+            call_script("function main() { return a.b; }", "main", ""),
+            Err(ScriptError::Runtime(_))
+        ));
+        assert!(matches!(
+            call_script("function other() {}", "main", ""),
+            Err(ScriptError::Runtime(_))
+        ));
+        assert!(matches!(
+            call_script_json("function main(d) { return d; }", "main", "{"),
+            Err(ScriptError::Runtime(_))
+        ));
+    }
+
+    #[test]
+    fn test_capture_console() {
+        let sandbox = JsSandbox::new(ScriptLimits::default()).unwrap();
+        sandbox.capture_console().unwrap();
+        sandbox
+            .load(r#"console.log("loaded"); function main(d) { console.warn("data", { a: d }); return 1; }"#)
+            .unwrap();
+        sandbox.call("main", "x").unwrap();
+        assert_eq!(
+            sandbox.take_console().unwrap(),
+            vec!["[log] loaded", r#"[warn] data {"a":"x"}"#]
+        );
+        assert!(sandbox.take_console().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_noop_console() {
+        // 未开启收集时 console 调用不报错
+        let script = r#"function main(d) { console.log("data", d); return d; }"#;
+        assert_eq!(call_script(script, "main", "x").unwrap(), r#""x""#);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let sandbox = JsSandbox::new(limits(100)).unwrap();
+        sandbox
+            .load("function main() { while (true) {} } function ok() { return 1; }")
+            .unwrap();
+        assert_eq!(sandbox.call("main", ""), Err(ScriptError::Timeout(100)));
+        // 超时后沙箱仍可继续使用
+        assert_eq!(sandbox.call("ok", "").unwrap(), "1");
+
+        assert_eq!(
+            JsSandbox::new(limits(100)).unwrap().load("while (true) {}"),
+            Err(ScriptError::Timeout(100))
+        );
+    }
+
+    #[test]
+    fn test_memory_and_stack_limit() {
+        let sandbox = JsSandbox::new(ScriptLimits {
+            memory_limit: 4 * 1024 * 1024,
+            ..ScriptLimits::default()
+        })
+        .unwrap();
+        sandbox
+            .load(
+                "function main() { var a = []; while (true) { a.push(new Array(1024).fill(1)); } }",
+            )
+            .unwrap();
+        assert_eq!(sandbox.call("main", ""), Err(ScriptError::OutOfMemory));
+
+        let sandbox = JsSandbox::new(ScriptLimits::default()).unwrap();
+        sandbox.load("function main() { return main(); }").unwrap();
+        assert_eq!(sandbox.call("main", ""), Err(ScriptError::StackOverflow));
+    }
+
+    #[test]
+    fn test_classify_exception() {
+        assert_eq!(
+            classify_exception("SyntaxError", "unexpected token", true),
+            ScriptError::Syntax("unexpected token".to_string())
+        );
+        assert_eq!(
+            classify_exception("SyntaxError", "unexpected token", false),
+            ScriptError::Runtime("SyntaxError: unexpected token".to_string())
+        );
+        assert_eq!(
+            classify_exception("InternalError", "stack overflow", false),
+            ScriptError::StackOverflow
+        );
+        assert_eq!(
+            classify_exception("InternalError", "out of memory", false),
+            ScriptError::OutOfMemory
+        );
+    }
+}
@@ -1,5 +1,10 @@
+pub mod alarm_utils;
+pub mod calc_schedule;
 pub mod config;
+pub mod flux_query;
 pub mod influxdb_utils;
+pub mod js_pool;
+pub mod js_sandbox;
 pub mod models;
 pub mod mongo_utils;
 pub mod mysql_utils;
@@ -7,7 +12,11 @@ pub mod rabbit_utils;
 pub mod redis_handler;
 pub mod redis_lock;
 pub mod redis_pool_utils;
+pub mod script_dry_run;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 pub mod time_utils;
+pub mod unit_utils;
 
 pub fn init_logger() {
     log4rs::init_file("log4rs.yml", Default::default()).unwrap();
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use serde_json;
-#[derive(Serialize, Deserialize, Debug)]
+use std::collections::HashMap;
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DataRowList {
     pub Time: i64,                  // 秒级时间戳
     pub DeviceUid: String,          // 能够产生网络通讯的唯一编码
@@ -24,10 +25,211 @@ impl MQTTMessage {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DataRow {
-    pub Name: String,  // 数据行名称
-    pub Value: String, // 数据行值
+    pub Name: String,       // 数据行名称
+    pub Value: SignalValue, // 数据行值, 兼容字符串
+}
+
+/// 信号值, 脚本返回的 JSON 值按原类型解析, 字符串在入库时按信号配置的类型转换
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignalValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Json(serde_json::Value),
+    Geo(GeoPoint),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// 信号配置的值类型, 对应 Signal.type
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SignalType {
+    Bool,
+    Int,
+    Float,
+    #[default]
+    Text,
+    Json,
+    Geo,
+}
+
+impl SignalType {
+    /// 原有的中文类型名只有 "数字" 按浮点数写入, 其余(包括 "整数"、"布尔")仍按文本写入,
+    /// 避免同一信号在 InfluxDB 中已有的字符串字段改为数值/布尔后写入被拒绝.
+    /// 整数和布尔字段需要显式使用新的类型名 int/bool, 并为信号使用新的 id 或在新的 bucket 中写入.
+    /// json/geo 仍按文本写入, 只影响解析方式
+    pub fn from_type_name(name: &str) -> SignalType {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "数字" | "float" => SignalType::Float,
+            "int" => SignalType::Int,
+            "bool" => SignalType::Bool,
+            "json" | "对象" => SignalType::Json,
+            "geo" | "位置" | "坐标" => SignalType::Geo,
+            _ => SignalType::Text,
+        }
+    }
+}
+
+impl SignalValue {
+    /// 数值形式, 布尔值为 1/0, 用于报警判断和计算参数
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            SignalValue::Bool(v) => Some(if *v { 1.0 } else { 0.0 }),
+            SignalValue::Int(v) => Some(*v as f64),
+            SignalValue::Float(v) => Some(*v),
+            SignalValue::Text(v) => v.trim().parse().ok(),
+            SignalValue::Json(v) => v.as_f64(),
+            SignalValue::Geo(_) => None,
+        }
+    }
+
+    /// 按信号类型转换, 无法转换时返回 None
+    pub fn coerce(&self, signal_type: SignalType) -> Option<SignalValue> {
+        match signal_type {
+            SignalType::Float => self.as_f64().map(SignalValue::Float),
+            SignalType::Int => {
+                // 小数部分为 0 的浮点数也接受, 例如 "12.0"
+                let whole =
+                    |v: f64| (v.fract() == 0.0 && v.abs() < i64::MAX as f64).then_some(v as i64);
+                let int = match self {
+                    SignalValue::Int(v) => Some(*v),
+                    SignalValue::Text(v) => v.trim().parse::<i64>().ok(),
+                    _ => None,
+                };
+                int.or_else(|| self.as_f64().and_then(whole))
+                    .map(SignalValue::Int)
+            }
+            SignalType::Bool => match self {
+                SignalValue::Bool(v) => Some(SignalValue::Bool(*v)),
+                SignalValue::Int(v) if *v == 0 || *v == 1 => Some(SignalValue::Bool(*v == 1)),
+                SignalValue::Text(v) => match v.trim().to_ascii_lowercase().as_str() {
+                    "true" | "1" | "on" => Some(SignalValue::Bool(true)),
+                    "false" | "0" | "off" => Some(SignalValue::Bool(false)),
+                    _ => None,
+                },
+                SignalValue::Json(serde_json::Value::Bool(v)) => Some(SignalValue::Bool(*v)),
+                _ => None,
+            },
+            SignalType::Text => Some(SignalValue::Text(self.to_string())),
+            SignalType::Json => match self {
+                SignalValue::Text(v) => serde_json::from_str(v).ok().map(SignalValue::Json),
+                _ => serde_json::to_value(self).ok().map(SignalValue::Json),
+            },
+            SignalType::Geo => match self {
+                SignalValue::Geo(v) => Some(SignalValue::Geo(*v)),
+                SignalValue::Text(v) => {
+                    let (lat, lon) = v.split_once(',')?;
+                    Some(SignalValue::Geo(GeoPoint {
+                        lat: lat.trim().parse().ok()?,
+                        lon: lon.trim().parse().ok()?,
+                    }))
+                }
+                SignalValue::Json(v) => {
+                    serde_json::from_value(v.clone()).ok().map(SignalValue::Geo)
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// 写入 InfluxDB 的字段值, JSON 和坐标以 JSON 字符串保存
+    pub fn to_data_value(&self) -> DataValue {
+        match self {
+            SignalValue::Bool(v) => DataValue::Bool(*v),
+            SignalValue::Int(v) => DataValue::Integer(*v),
+            SignalValue::Float(v) => DataValue::Float(*v),
+            SignalValue::Text(v) => DataValue::Text(v.clone()),
+            SignalValue::Json(v) => DataValue::Text(v.to_string()),
+            SignalValue::Geo(v) => DataValue::Text(serde_json::to_string(v).unwrap_or_default()),
+        }
+    }
+}
+
+impl std::fmt::Display for SignalValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignalValue::Bool(v) => write!(f, "{}", v),
+            SignalValue::Int(v) => write!(f, "{}", v),
+            SignalValue::Float(v) => write!(f, "{}", v),
+            SignalValue::Text(v) => write!(f, "{}", v),
+            SignalValue::Json(v) => write!(f, "{}", v),
+            SignalValue::Geo(v) => write!(f, "{},{}", v.lat, v.lon),
+        }
+    }
+}
+
+impl Serialize for SignalValue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            SignalValue::Bool(v) => serializer.serialize_bool(*v),
+            SignalValue::Int(v) => serializer.serialize_i64(*v),
+            SignalValue::Float(v) => serializer.serialize_f64(*v),
+            SignalValue::Text(v) => serializer.serialize_str(v),
+            SignalValue::Json(v) => v.serialize(serializer),
+            SignalValue::Geo(v) => v.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SignalValue {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        Ok(match value {
+            serde_json::Value::Bool(v) => SignalValue::Bool(v),
+            serde_json::Value::String(v) => SignalValue::Text(v),
+            serde_json::Value::Number(ref n) => match n.as_i64() {
+                Some(v) => SignalValue::Int(v),
+                None => SignalValue::Float(n.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::Object(ref map)
+                if map.len() == 2 && map.contains_key("lat") && map.contains_key("lon") =>
+            {
+                match serde_json::from_value::<GeoPoint>(value.clone()) {
+                    Ok(geo) => SignalValue::Geo(geo),
+                    Err(_) => SignalValue::Json(value),
+                }
+            }
+            _ => SignalValue::Json(value),
+        })
+    }
+}
+
+impl From<&str> for SignalValue {
+    fn from(value: &str) -> Self {
+        SignalValue::Text(value.to_string())
+    }
+}
+
+impl From<String> for SignalValue {
+    fn from(value: String) -> Self {
+        SignalValue::Text(value)
+    }
+}
+
+impl From<f64> for SignalValue {
+    fn from(value: f64) -> Self {
+        SignalValue::Float(value)
+    }
+}
+
+impl From<i64> for SignalValue {
+    fn from(value: i64) -> Self {
+        SignalValue::Int(value)
+    }
+}
+
+impl From<bool> for SignalValue {
+    fn from(value: bool) -> Self {
+        SignalValue::Bool(value)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -101,11 +303,12 @@ impl CoapMessage {
         serde_json::to_string(self).expect("Failed to serialize CoapMessage")
     }
 }
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum DataValue {
     Float(f64),
     Text(String),
     Integer(i64),
+    Bool(bool),
 }
 #[derive(Serialize, Deserialize)]
 pub struct Signal {
@@ -114,7 +317,32 @@ pub struct Signal {
     #[serde(rename = "ID")] // 在序列化时使用 "ID"
     pub id: i64,
     pub r#type: String,
-    unit: Option<String>,
+    pub unit: Option<String>, // 入库单位
+    #[serde(default)]
+    pub transform: Option<SignalTransform>,
+}
+
+/// 入库前对数值信号的变换, 依次执行线性变换、单位换算和范围限制
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SignalTransform {
+    #[serde(default)]
+    pub scale: Option<f64>, // value * scale + offset
+    #[serde(default)]
+    pub offset: Option<f64>,
+    #[serde(default)]
+    pub source_unit: Option<String>, // 设备上报的单位, 换算为 Signal.unit
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+    #[serde(default)]
+    pub keep_raw: bool, // 同时保存原始值, 字段名为 {信号id}_raw
+}
+
+impl Signal {
+    pub fn value_type(&self) -> SignalType {
+        SignalType::from_type_name(self.r#type.as_str())
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -131,13 +359,57 @@ pub struct SignalWaringConfig {
     pub unit: Option<String>, // 单位
     #[serde(rename = "ID")]
     pub id: i32, // ID
+    #[serde(default)]
+    pub deadband: f64, // 滞回宽度, 报警中需越过边界该宽度才恢复
+    #[serde(default)]
+    pub clear_min: Option<f64>, // 恢复阈值, 小值, 设置后替代 min ± deadband
+    #[serde(default)]
+    pub clear_max: Option<f64>, // 恢复阈值, 大值, 设置后替代 max ± deadband
+    #[serde(default)]
+    pub hold_seconds: i64, // 条件需持续的秒数, 0 不限制
+    #[serde(default)]
+    pub hold_samples: u32, // 条件需连续命中的样本数, 0 或 1 不限制
+    #[serde(default)]
+    pub severity: AlarmSeverity, // 报警级别
+    #[serde(default)]
+    pub kind: ThresholdKind, // 评估值类型, min/max 作用于该值
+    #[serde(default)]
+    pub rate_unit: RateUnit, // kind 为 rate 时的时间单位
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ThresholdKind {
+    #[default]
+    Range, // 当前值
+    Delta, // 与上一样本的差值, 绝对差值使用 min=-x max=x 范围外报警
+    Rate,  // 与上一样本的变化率
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RateUnit {
+    #[default]
+    Second,
+    Minute,
+}
+
+impl RateUnit {
+    pub fn seconds(&self) -> f64 {
+        match self {
+            RateUnit::Second => 1.0,
+            RateUnit::Minute => 60.0,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct SignalMapping {
     pub cache_size: u64,
     pub id: i64,
-    pub numb: bool,
+    pub value_type: SignalType,
+    pub unit: Option<String>,
+    pub transform: Option<SignalTransform>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -166,14 +438,288 @@ pub struct SignalDelayWaring {
     pub script: String,
     #[serde(rename = "ID")]
     pub id: i32,
+    #[serde(default)]
+    pub severity: AlarmSeverity, // 报警级别
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq)]
 pub struct Tv {
     pub time: i64,
     pub value: f64,
 }
 
+/// 信号最近两次样本, 存储在 signal_last_sample:{uid}:{code} 哈希中, 字段为信号 id, 时间为设备上报时间
+///
+/// signal_delay_warning:* 以值为成员, 连续相同的值只保留一个, 无法用于差值/变化率判断
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+pub struct SignalSamples {
+    pub last: Tv,
+    pub prev: Option<Tv>,
+}
+
+/// 报警事件交换机, 通知、转发、界面等消费者各自绑定队列订阅
+pub const ALARM_EXCHANGE: &str = "alarm_event";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlarmRuleType {
+    Threshold, // 阈值报警 SignalWaringConfig
+    Script,    // 脚本报警 SignalDelayWaring
+    Offline,   // 离线报警 OfflineConfig
+}
+
+impl AlarmRuleType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlarmRuleType::Threshold => "threshold",
+            AlarmRuleType::Script => "script",
+            AlarmRuleType::Offline => "offline",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AlarmSeverity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl AlarmSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlarmSeverity::Info => "info",
+            AlarmSeverity::Warning => "warning",
+            AlarmSeverity::Critical => "critical",
+        }
+    }
+}
+
+impl std::str::FromStr for AlarmSeverity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "info" => Ok(AlarmSeverity::Info),
+            "warning" => Ok(AlarmSeverity::Warning),
+            "critical" => Ok(AlarmSeverity::Critical),
+            _ => Err(format!("unknown severity: {}", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum AlarmStatus {
+    #[default]
+    Firing, // 报警中
+    Resolved, // 已恢复
+}
+
+/// 单个 (规则, 设备, 信号) 的报警状态, 存储在 alarm_state:* 键中
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlarmState {
+    pub status: AlarmStatus,
+    pub fired_at: i64,  // 本轮报警触发时间, 秒级时间戳
+    pub last_time: i64, // 最近一次评估的数据时间
+    pub count: u64,     // 本轮报警期间命中次数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<i64>, // 恢复时间
+}
+
+impl AlarmState {
+    /// 报警持续时间(秒), 未恢复时计算到最近一次数据时间
+    pub fn duration(&self) -> i64 {
+        self.resolved_at.unwrap_or(self.last_time) - self.fired_at
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AlarmEvent {
+    pub rule_id: i32,                // 报警规则ID
+    pub rule_type: AlarmRuleType,    // 报警规则类型
+    pub device_uid: String,          // 设备唯一编码
+    pub identification_code: String, // 设备标识码
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>, // 协议
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal_id: Option<i32>, // 信号ID, 脚本报警为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal_name: Option<String>, // 信号名称, 脚本报警为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>, // 触发报警的值, 脚本报警为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub param: Option<HashMap<String, Vec<Tv>>>, // 脚本报警的入参
+    pub time: i64,                   // 数据上报时间, 秒级时间戳
+    pub insert_time: i64,            // 报警产生时间, 秒级时间戳
+    #[serde(default)]
+    pub severity: AlarmSeverity, // 报警级别
+    #[serde(default)]
+    pub status: AlarmStatus, // 状态迁移: FIRING 触发, RESOLVED 恢复
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<i64>, // 恢复时报警持续时间(秒)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alarm_id: Option<String>, // 对应 AlarmRecord 的ID
+    #[serde(default)]
+    pub silenced: bool, // 命中静默规则, 照常记录和发布, 不通知
+    #[serde(default)]
+    pub escalation_level: u32, // 升级通知层级, 0 为首次通知
+}
+
+impl AlarmEvent {
+    /// 路由键 alarm.{rule_type}.{severity}, 例如 alarm.threshold.critical
+    pub fn routing_key(&self) -> String {
+        format!(
+            "alarm.{}.{}",
+            self.rule_type.as_str(),
+            self.severity.as_str()
+        )
+    }
+
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).expect("Failed to serialize AlarmEvent")
+    }
+}
+
+/// 报警规则与机器人的绑定关系, 存储在 notice_bind:{rule_type}:{rule_id} 列表中
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NoticeBind {
+    pub robot_type: String,       // feishu, dingtalk
+    pub robot_id: i64,            // 对应 notice:{robot_type} 哈希字段
+    pub template: Option<String>, // 消息模板, 为空使用默认模板
+}
+
+/// 升级层级: 报警触发后 after_minutes 分钟仍未确认则通知该层级
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EscalationTier {
+    pub after_minutes: i64,      // 相对报警触发时间
+    pub notice: Vec<NoticeBind>, // 该层级的通知对象
+}
+
+/// 报警升级策略, 存储在 escalation_policy 哈希中, 字段为 {rule_type}:{rule_id}
+/// 最后一个层级按 repeat_minutes 重复通知, 为 0 则不重复
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EscalationPolicy {
+    pub tiers: Vec<EscalationTier>,
+    #[serde(default)]
+    pub repeat_minutes: i64,
+}
+
+/// 待执行的升级任务, 作为 escalation_queue 有序集合的成员, 分值为执行时间
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EscalationTask {
+    pub alarm_id: String,
+    pub rule_type: AlarmRuleType,
+    pub rule_id: i32,
+    pub level: u32, // 待通知的层级, 从 1 开始
+}
+
+/// 离线报警配置, 存储在 offline_config 哈希中
+/// 字段为 {protocol}:{device_uid}:{identification_code} (设备) 或 product:{product} (产品)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OfflineConfig {
+    #[serde(rename = "ID")]
+    pub id: i32, // ID
+    pub timeout_seconds: i64, // 超过该时间未上报视为离线
+    #[serde(default)]
+    pub severity: AlarmSeverity, // 报警级别
+    #[serde(default = "default_enable")]
+    pub enable: bool, // 是否启用
+}
+
+/// 报警静默规则, 存储在 silence_rule 列表中
+/// 设置的匹配条件需全部满足, 时间窗口为 [start, end] 或 cron 触发后持续 duration_seconds
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SilenceRule {
+    #[serde(rename = "ID")]
+    pub id: i32, // ID
+    pub device_uid: Option<String>,       // 设备
+    pub group: Option<String>,            // 设备组, 成员存储在 device_group:{group} 集合中
+    pub signal_id: Option<i32>,           // 信号
+    pub rule_id: Option<i32>,             // 报警规则
+    pub rule_type: Option<AlarmRuleType>, // 报警规则类型, 与 rule_id 配合使用
+    pub start: Option<i64>,               // 开始时间, 秒级时间戳
+    pub end: Option<i64>,                 // 结束时间, 秒级时间戳
+    pub cron: Option<String>,             // 周期窗口开始时间, 按 UTC 计算
+    pub duration_seconds: Option<i64>,    // 周期窗口持续时间
+    pub comment: Option<String>,          // 备注, 例如维护内容
+}
+
+/// 报警处理流程: 待处理 -> 已确认 -> 已关闭
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum AlarmLifecycle {
+    #[default]
+    Open,
+    Acknowledged,
+    Closed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AlarmComment {
+    pub operator: String, // 操作人
+    pub comment: String,  // 备注内容
+    pub time: i64,        // 秒级时间戳
+}
+
+/// 一次报警 (FIRING 到 RESOLVED) 的记录, 承载确认、关闭、备注等处理流程
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AlarmRecord {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub rule_id: i32,
+    pub rule_type: AlarmRuleType,
+    pub device_uid: String,
+    pub identification_code: String,
+    pub signal_id: Option<i32>,
+    pub signal_name: Option<String>,
+    pub value: Option<f64>, // 触发报警的值
+    pub severity: AlarmSeverity,
+    pub status: AlarmStatus,
+    pub fired_at: i64,
+    pub resolved_at: Option<i64>,
+    pub duration: Option<i64>,
+    #[serde(default)]
+    pub lifecycle: AlarmLifecycle,
+    pub acked_by: Option<String>,
+    pub acked_at: Option<i64>,
+    pub closed_by: Option<String>,
+    pub closed_at: Option<i64>,
+    #[serde(default)]
+    pub comments: Vec<AlarmComment>,
+    #[serde(default)]
+    pub silenced: bool, // 命中静默规则
+}
+
+impl AlarmRecord {
+    pub fn from_event(id: String, event: &AlarmEvent) -> Self {
+        AlarmRecord {
+            id,
+            rule_id: event.rule_id,
+            rule_type: event.rule_type,
+            device_uid: event.device_uid.clone(),
+            identification_code: event.identification_code.clone(),
+            signal_id: event.signal_id,
+            signal_name: event.signal_name.clone(),
+            value: event.value,
+            severity: event.severity,
+            status: event.status,
+            fired_at: event.time,
+            resolved_at: None,
+            duration: None,
+            lifecycle: AlarmLifecycle::Open,
+            acked_by: None,
+            acked_at: None,
+            closed_by: None,
+            closed_at: None,
+            comments: vec![],
+            silenced: event.silenced,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CalcCache {
     #[serde(rename = "id")]
@@ -182,6 +728,18 @@ pub struct CalcCache {
     pub cron: String,
     pub script: String,
     pub offset: i64,
+    #[serde(default)]
+    pub catch_up: CatchUpPolicy,
+}
+
+/// 调度器停机或积压导致错过执行时间后的补偿方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CatchUpPolicy {
+    #[default]
+    Latest, // 只补最近一次
+    All,  // 逐次补齐错过的执行时间
+    Skip, // 丢弃错过的执行, 等待下一次
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -199,95 +757,18 @@ pub struct CalcParamCache {
     pub calc_rule_id: i32,
 }
 
-#[derive(Debug)]
-pub struct InfluxQueryConfig {
-    pub bucket: String,
-    pub measurement: String,
-    pub fields: Vec<String>,
-    pub start_time: i64,
-    pub end_time: i64,
-    pub aggregation: AggregationConfig,
-    pub reduce: String, // sum, min, max, mean
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TransmitBind {
+    #[serde(rename = "ID")]
+    pub id: i64, // ID
+    pub sink_type: String, // 转发目标类型: clickhouse, mysql, mongo, cassandra, influxdb, webhook
+    pub sink_id: i64,      // 转发目标配置ID, 对应 transmit:{sink_type} 哈希字段
+    #[serde(default = "default_enable")]
+    pub enable: bool, // 是否启用
 }
 
-#[derive(Debug)]
-pub struct AggregationConfig {
-    pub every: i32,
-    pub function: String, // e.g., "mean", "sum", "min", "max"
-    pub create_empty: bool,
-}
-
-impl InfluxQueryConfig {
-    // Generate a Flux query based on the InfluxQueryConfig
-    pub fn generate_flux_query(&self) -> String {
-        let filters: Vec<String> = self
-            .fields
-            .iter()
-            .map(|field| format!(r#"r["_field"] == "{}""#, field))
-            .collect();
-
-        let time_range = if self.start_time != 0 && self.end_time != 0 {
-            format!(r#"start: {}, stop: {}"#, self.start_time, self.end_time)
-        } else {
-            String::new()
-        };
-
-        let filter_clause = if !filters.is_empty() {
-            format!(r#"|> filter(fn: (r) => {})"#, filters.join(" or "))
-        } else {
-            String::new()
-        };
-
-        format!(
-            r#"
-            from(bucket: "{}")
-                |> range({})
-                {}
-                |> filter(fn: (r) => r["_measurement"] == "{}")
-                |> aggregateWindow(every: {}s, fn: {}, createEmpty: {})
-                |> yield(name: "mean")
-            "#,
-            self.bucket,
-            time_range,
-            filter_clause,
-            self.measurement,
-            self.aggregation.every,
-            self.aggregation.function,
-            self.aggregation.create_empty
-        )
-    }
-
-    // Generate a Flux reduce query based on the InfluxQueryConfig
-    pub fn generate_flux_reduce(&self) -> String {
-        let filters: Vec<String> = self
-            .fields
-            .iter()
-            .map(|field| format!(r#"r["_field"] == "{}""#, field))
-            .collect();
-
-        let time_range = if self.start_time != 0 && self.end_time != 0 {
-            format!(r#"start: {}, stop: {}"#, self.start_time, self.end_time)
-        } else {
-            String::new()
-        };
-
-        let filter_clause = if !filters.is_empty() {
-            format!(r#"|> filter(fn: (r) => {})"#, filters.join(" or "))
-        } else {
-            String::new()
-        };
-
-        format!(
-            r#"
-            from(bucket: "{}")
-                |> range({})
-                {}
-                |> filter(fn: (r) => r["_measurement"] == "{}")
-                |> {}()
-            "#,
-            self.bucket, time_range, filter_clause, self.measurement, self.reduce
-        )
-    }
+fn default_enable() -> bool {
+    true
 }
 
 #[cfg(test)]
@@ -295,54 +776,127 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_generate_flux_query() {
-        let aggregation_config = AggregationConfig {
-            every: 60,
-            function: String::from("mean"),
-            create_empty: true,
-        };
-
-        let query_config = InfluxQueryConfig {
-            bucket: String::from("my_bucket"),
-            measurement: String::from("temperature"),
-            fields: vec![String::from("value")],
-            start_time: 1627848123,
-            end_time: 1627851723,
-            aggregation: aggregation_config,
-            reduce: String::from("sum"),
+    fn test_alarm_event_serialize() {
+        let event = AlarmEvent {
+            rule_id: 3,
+            rule_type: AlarmRuleType::Threshold,
+            device_uid: "1".to_string(),
+            identification_code: "2".to_string(),
+            protocol: Some("MQTT".to_string()),
+            signal_id: Some(5),
+            signal_name: Some("Temperature".to_string()),
+            value: Some(35.5),
+            param: None,
+            time: 1730000000,
+            insert_time: 1730000001,
+            severity: AlarmSeverity::Critical,
+            status: AlarmStatus::Resolved,
+            duration: Some(60),
+            alarm_id: None,
+            silenced: false,
+            escalation_level: 0,
         };
-
-        let flux_query = query_config.generate_flux_query();
-        println!("Generated Flux Query:\n{}", flux_query);
-
-        assert!(flux_query.contains("from(bucket: \"my_bucket\")"));
-        assert!(flux_query.contains("|> range(start: 1627848123, stop: 1627851723)"));
-        assert!(flux_query.contains("|> aggregateWindow(every: 60s, fn: mean, createEmpty: true)"));
+        assert_eq!(event.routing_key(), "alarm.threshold.critical");
+
+        let json: serde_json::Value = serde_json::from_str(&event.to_json_string()).unwrap();
+        assert_eq!(json["rule_type"], "threshold");
+        assert_eq!(json["severity"], "critical");
+        assert!(json.get("param").is_none());
+        assert_eq!(json["status"], "RESOLVED");
+
+        let parsed: AlarmEvent =
+            serde_json::from_str(r#"{"rule_id":1,"rule_type":"script","device_uid":"1","identification_code":"2","time":0,"insert_time":0}"#)
+                .unwrap();
+        assert_eq!(parsed.rule_type, AlarmRuleType::Script);
+        assert_eq!(parsed.severity, AlarmSeverity::Warning);
+        assert_eq!(parsed.status, AlarmStatus::Firing);
+        assert_eq!(
+            "Critical".parse::<AlarmSeverity>(),
+            Ok(AlarmSeverity::Critical)
+        );
+        assert!("fatal".parse::<AlarmSeverity>().is_err());
     }
 
     #[test]
-    fn test_generate_flux_reduce() {
-        let aggregation_config = AggregationConfig {
-            every: 60,
-            function: String::from("mean"),
-            create_empty: true,
-        };
-
-        let query_config = InfluxQueryConfig {
-            bucket: String::from("my_bucket"),
-            measurement: String::from("temperature"),
-            fields: vec![String::from("value")],
-            start_time: 1627848123,
-            end_time: 1627851723,
-            aggregation: aggregation_config,
-            reduce: String::from("sum"),
-        };
-
-        let flux_reduce_query = query_config.generate_flux_reduce();
-        println!("Generated Flux Reduce Query:\n{}", flux_reduce_query);
+    fn test_signal_value_serde() {
+        let dt: DataRowList = serde_json::from_str(
+            r#"{"Time":1,"DeviceUid":"1","IdentificationCode":"2","Nc":"","DataRows":[
+                {"Name":"a","Value":"23.5"},
+                {"Name":"b","Value":23.5},
+                {"Name":"c","Value":7},
+                {"Name":"d","Value":true},
+                {"Name":"e","Value":{"mode":"auto"}},
+                {"Name":"f","Value":{"lat":31.2,"lon":121.5}}]}"#,
+        )
+        .unwrap();
+        let values: Vec<SignalValue> = dt.DataRows.iter().map(|r| r.Value.clone()).collect();
+        assert_eq!(
+            values,
+            vec![
+                SignalValue::Text("23.5".to_string()),
+                SignalValue::Float(23.5),
+                SignalValue::Int(7),
+                SignalValue::Bool(true),
+                SignalValue::Json(serde_json::json!({"mode": "auto"})),
+                SignalValue::Geo(GeoPoint {
+                    lat: 31.2,
+                    lon: 121.5
+                }),
+            ]
+        );
+
+        // 字符串值原样序列化, 兼容旧的消费者
+        let json = serde_json::to_value(&dt.DataRows).unwrap();
+        assert_eq!(json[0]["Value"], "23.5");
+        assert_eq!(json[1]["Value"], 23.5);
+        assert_eq!(json[5]["Value"]["lon"], 121.5);
+    }
 
-        assert!(flux_reduce_query.contains("from(bucket: \"my_bucket\")"));
-        assert!(flux_reduce_query.contains("|> range(start: 1627848123, stop: 1627851723)"));
-        assert!(flux_reduce_query.contains("|> sum()"));
+    #[test]
+    fn test_signal_value_coerce() {
+        assert_eq!(SignalType::from_type_name("数字"), SignalType::Float);
+        assert_eq!(SignalType::from_type_name("Bool"), SignalType::Bool);
+        assert_eq!(SignalType::from_type_name("文本"), SignalType::Text);
+        // 原有类型名保持按文本写入
+        assert_eq!(SignalType::from_type_name("整数"), SignalType::Text);
+        assert_eq!(SignalType::from_type_name("布尔"), SignalType::Text);
+
+        let text = SignalValue::from("12");
+        assert_eq!(
+            text.coerce(SignalType::Float),
+            Some(SignalValue::Float(12.0))
+        );
+        assert_eq!(text.coerce(SignalType::Int), Some(SignalValue::Int(12)));
+        assert_eq!(SignalValue::from("12.5").coerce(SignalType::Int), None);
+        assert_eq!(SignalValue::from("hot").coerce(SignalType::Float), None);
+        assert_eq!(
+            SignalValue::from("on").coerce(SignalType::Bool),
+            Some(SignalValue::Bool(true))
+        );
+        assert_eq!(
+            SignalValue::Int(0).coerce(SignalType::Bool),
+            Some(SignalValue::Bool(false))
+        );
+        assert_eq!(
+            SignalValue::Float(1.5).coerce(SignalType::Text),
+            Some(SignalValue::from("1.5"))
+        );
+        assert_eq!(
+            SignalValue::from("31.2, 121.5").coerce(SignalType::Geo),
+            Some(SignalValue::Geo(GeoPoint {
+                lat: 31.2,
+                lon: 121.5
+            }))
+        );
+        assert_eq!(
+            SignalValue::from(r#"{"a":1}"#).coerce(SignalType::Json),
+            Some(SignalValue::Json(serde_json::json!({"a": 1})))
+        );
+
+        assert_eq!(SignalValue::Bool(true).as_f64(), Some(1.0));
+        assert!(matches!(
+            SignalValue::Json(serde_json::json!([1])).to_data_value(),
+            DataValue::Text(v) if v == "[1]"
+        ));
     }
 }
@@ -97,6 +97,8 @@ impl MongoDBManager {
 
 use crate::rabbit_utils::RabbitMQ;
 use log::info;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Build, Rocket};
 use std::sync::Arc;
 use tokio::sync::{Mutex, MutexGuard, OnceCell};
 
@@ -115,6 +117,33 @@ pub async fn get_mongo() -> Result<MutexGuard<'static, MongoDBManager>, Box<dyn
         .ok_or("DB Manager has not been initialized")?;
     Ok(instance.lock().await)
 }
+
+/// Rocket 启动时创建 MongoDBManager 并交给 State 管理
+pub struct MongoFairing {
+    pub config: MongoConfig,
+}
+
+#[rocket::async_trait]
+impl Fairing for MongoFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "MongoDB Initializer",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+        let result = MongoDBManager::new(self.config.clone()).await;
+        match result {
+            Ok(db_manager) => Ok(rocket.manage(db_manager)),
+            Err(e) => {
+                eprintln!("Failed to initialize MongoDB: {:?}", e);
+                Err(rocket)
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
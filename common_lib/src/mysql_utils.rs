@@ -1,9 +1,10 @@
 use crate::config::MySQLConfig;
 use log::{error, info};
 use r2d2_mysql::{
-    mysql::{prelude::*, Opts, OptsBuilder},
+    mysql::{prelude::*, Opts, OptsBuilder, Params, TxOpts, Value},
     r2d2, MySqlConnectionManager,
 };
+use std::error::Error;
 use std::{sync::Arc, thread};
 use urlencoding::encode;
 #[derive(Debug, Clone)]
@@ -16,9 +17,43 @@ impl MysqlOp {
         let pool = create_db_pool(&config);
         Self { pool }
     }
+
+    pub fn try_new(config: MySQLConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let pool = try_create_db_pool(&config)?;
+        Ok(Self { pool })
+    }
+
+    /// 执行单条语句
+    pub fn execute(&self, sql: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut conn = self.pool.get()?;
+        conn.query_drop(sql)?;
+        Ok(())
+    }
+
+    /// 在同一个事务中按 chunk_size 分批执行, 任一失败则整体回滚
+    pub fn exec_batch_in_transaction(
+        &self,
+        sql: &str,
+        params: Vec<Vec<Value>>,
+        chunk_size: usize,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.start_transaction(TxOpts::default())?;
+        for chunk in params.chunks(chunk_size.max(1)) {
+            tx.exec_batch(sql, chunk.iter().cloned().map(Params::Positional))?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
 }
 
 fn create_db_pool(config: &MySQLConfig) -> r2d2::Pool<MySqlConnectionManager> {
+    try_create_db_pool(config).expect("Failed to create pool")
+}
+
+fn try_create_db_pool(
+    config: &MySQLConfig,
+) -> Result<r2d2::Pool<MySqlConnectionManager>, Box<dyn Error + Send + Sync>> {
     let encoded_username = encode(&config.username);
     let encoded_password = encode(&config.password);
 
@@ -32,18 +67,15 @@ fn create_db_pool(config: &MySQLConfig) -> r2d2::Pool<MySqlConnectionManager> {
         config.dbname
     );
 
-    let opts = Opts::from_url(&url).expect("Invalid database URL");
+    let opts = Opts::from_url(&url)?;
     let builder = OptsBuilder::from_opts(opts);
     let manager = MySqlConnectionManager::new(builder);
 
     info!("Creating MySQL connection pool...");
-    let pool = r2d2::Pool::builder()
-        .max_size(4)
-        .build(manager)
-        .expect("Failed to create pool");
+    let pool = r2d2::Pool::builder().max_size(4).build(manager)?;
     info!("Connection pool created successfully.");
 
-    pool
+    Ok(pool)
 }
 
 pub fn run_db_task(pool: r2d2::Pool<MySqlConnectionManager>) {
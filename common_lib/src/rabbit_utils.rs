@@ -1,4 +1,5 @@
 use crate::config::MqConfig;
+use crate::models::ALARM_EXCHANGE;
 use crate::redis_handler::get_redis_instance;
 use futures_util::stream::StreamExt;
 use lapin::options::{BasicAckOptions, BasicConsumeOptions};
@@ -9,7 +10,6 @@ use lapin::{
     BasicProperties, Channel, Connection, ConnectionProperties, ExchangeKind,
 };
 use log::{debug, error, info};
-use quick_js::Context;
 use r2d2::Pool;
 use r2d2_redis::RedisConnectionManager;
 use std::error::Error;
@@ -142,7 +142,7 @@ impl RabbitMQ {
 
     pub async fn consume2<F, Fut>(&self, queue_name: &str, handler: F) -> Result<(), Box<dyn Error>>
     where
-        F: Fn(lapin::message::Delivery, quick_js::Context) -> Fut + Send + Sync + 'static,
+        F: Fn(lapin::message::Delivery) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = Result<(), Box<dyn Error>>> + Send + 'static,
     {
         let mut consumer = self
@@ -160,11 +160,8 @@ impl RabbitMQ {
             if let Ok(delivery) = delivery {
                 let delivery_tag = delivery.delivery_tag;
 
-                // 在这里为每条消息创建独立的状态
-                let context = Context::new().unwrap();
-
-                // 调用传入的异步处理函数，传递独立的上下文
-                match handler(delivery, context).await {
+                // 调用传入的异步处理函数
+                match handler(delivery).await {
                     Ok(()) => {
                         // 处理成功，确认消息
                         self.channel
@@ -229,6 +226,22 @@ impl RabbitMQ {
     }
 }
 
+/// 声明报警事件交换机 (topic, 持久化), 生产者与消费者使用相同参数声明
+pub async fn declare_alarm_exchange(channel: &Channel) -> Result<(), Box<dyn Error>> {
+    channel
+        .exchange_declare(
+            ALARM_EXCHANGE,
+            ExchangeKind::Topic,
+            lapin::options::ExchangeDeclareOptions {
+                durable: true,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+    Ok(())
+}
+
 static RABBIT_MQ_INSTANCE: OnceCell<Arc<Mutex<RabbitMQ>>> = OnceCell::const_new();
 
 pub async fn init_rabbitmq(url: &str) -> Result<(), Box<dyn Error>> {
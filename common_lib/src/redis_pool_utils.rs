@@ -37,6 +37,31 @@ impl RedisOp {
         let mut con = self.get_connection();
         con.keys(key)
     }
+    /// 使用 SCAN 按模式遍历所有匹配的键, 每次最多检查 count 个键, 避免 KEYS 阻塞 Redis
+    pub fn scan_keys(&self, pattern: &str, count: usize) -> Result<Vec<String>, RedisError> {
+        let mut con = self.get_connection();
+        let mut cursor: u64 = 0;
+        let mut keys = Vec::new();
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(pattern)
+                .arg("COUNT")
+                .arg(count)
+                .query(&mut *con)?;
+            keys.extend(batch);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        // 遍历期间键有变化时 SCAN 可能重复返回同一个键
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
     pub fn acquire_lock(
         &self,
         lock_key: &str,
@@ -78,6 +103,59 @@ impl RedisOp {
         Ok(false)
     }
 
+    /// 获取或续期租约, 键不存在或已由 owner 持有时返回 true, 用于多实例选主
+    pub fn acquire_lease(&self, key: &str, owner: &str, ttl: u64) -> Result<bool, RedisError> {
+        let mut con = self.get_connection();
+        let set: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(owner)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl)
+            .query(&mut *con)?;
+        if set.is_some() {
+            return Ok(true);
+        }
+        // 比较与续期需要原子执行, 避免续期到其他实例刚拿到的租约
+        let renewed: i32 = redis::Script::new(
+            r"if redis.call('GET', KEYS[1]) == ARGV[1] then
+                return redis.call('EXPIRE', KEYS[1], ARGV[2])
+            end
+            return 0",
+        )
+        .key(key)
+        .arg(owner)
+        .arg(ttl)
+        .invoke(&mut *con)?;
+        Ok(renewed == 1)
+    }
+
+    /// 当前值等于 expected (None 表示键不存在) 时写入 value, 写入成功返回 true
+    pub fn compare_and_set(
+        &self,
+        key: &str,
+        expected: Option<&str>,
+        value: &str,
+    ) -> Result<bool, RedisError> {
+        let mut con = self.get_connection();
+        let set: i32 = redis::Script::new(
+            r"local current = redis.call('GET', KEYS[1])
+            if ARGV[1] == '0' then
+                if current then return 0 end
+            elseif current ~= ARGV[2] then
+                return 0
+            end
+            redis.call('SET', KEYS[1], ARGV[3])
+            return 1",
+        )
+        .key(key)
+        .arg(if expected.is_some() { "1" } else { "0" })
+        .arg(expected.unwrap_or(""))
+        .arg(value)
+        .invoke(&mut *con)?;
+        Ok(set == 1)
+    }
+
     // String 操作
     pub fn set_string(&self, key: &str, value: &str) -> Result<(), RedisError> {
         let mut con = self.get_connection();
@@ -168,6 +246,12 @@ impl RedisOp {
         }
     }
 
+    /// 获取分数不大于 max 的 Zset 成员, 按分数升序
+    pub fn get_zset_by_score(&self, key: &str, max: f64) -> Result<Vec<(String, f64)>, RedisError> {
+        let mut con = self.get_connection();
+        con.zrangebyscore_withscores(key, "-inf", max)
+    }
+
     /// 获取 Zset 长度
     pub fn get_zset_length(&self, key: &str) -> Result<u64, RedisError> {
         let mut con = self.get_connection();
@@ -186,6 +270,13 @@ impl RedisOp {
         }
     }
 
+    /// 删除 Zset 成员, 仅当本次调用删除了该成员时返回 true, 用于多实例争抢同一任务
+    pub fn claim_zset(&self, key: &str, member: &str) -> Result<bool, RedisError> {
+        let mut con = self.get_connection();
+        let removed: i32 = con.zrem(key, member)?;
+        Ok(removed == 1)
+    }
+
     /// 删除 Zset 中的第一个成员
     pub fn delete_first_zset_member(&self, key: &str) -> Result<(), RedisError> {
         let mut con = self.get_connection();
@@ -221,6 +312,12 @@ impl RedisOp {
             Err(e) => Err(e),
         }
     }
+    /// 哈希字段自增
+    pub fn incr_hash(&self, key: &str, field: &str, delta: i64) -> Result<i64, RedisError> {
+        let mut con = self.get_connection();
+        con.hincr(key, field, delta)
+    }
+
     pub fn get_hash_all_value(&self, key: &str) -> Result<Vec<String>, RedisError> {
         let mut con = self.get_connection();
 
@@ -0,0 +1,293 @@
+use crate::js_sandbox::{JsSandbox, ScriptLimits};
+use crate::models::{DataRowList, Signal};
+use crate::redis_pool_utils::RedisOp;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+
+/// 脚本类型: 解析脚本 main(message) 返回 DataRowList 数组, 报警脚本 main(param) 返回 bool
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptKind {
+    #[default]
+    Parse,
+    Alarm,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DryRunRequest {
+    #[serde(default)]
+    pub kind: ScriptKind,
+    pub script: String,
+    pub payload: String,          // 解析脚本为原始报文, 报警脚本为入参 JSON
+    pub protocol: Option<String>, // 解析结果的协议字段, 默认 MQTT
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignalCheckStatus {
+    Matched,      // 信号已配置
+    Unmapped,     // 信号未配置, 入库时会被忽略
+    InvalidValue, // 值无法转换为信号配置的类型
+    Missing,      // 已配置但本次未上报
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignalCheck {
+    pub device_uid: String,
+    pub identification_code: String,
+    pub name: String,
+    pub value: Option<String>,
+    pub signal_id: Option<i64>,
+    pub status: SignalCheckStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DryRunReport {
+    pub ok: bool,
+    pub output: Option<String>, // main 返回值的 JSON
+    pub rows: Vec<DataRowList>,
+    pub signals: Vec<SignalCheck>,
+    pub console: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+/// 按 handler_data_storage_string 的方式试运行脚本, redis 为空时跳过信号配置检查
+pub fn dry_run(request: &DryRunRequest, redis: Option<&RedisOp>) -> DryRunReport {
+    let mut report = DryRunReport::default();
+
+    let sandbox = match JsSandbox::new(ScriptLimits::default()) {
+        Ok(sandbox) => sandbox,
+        Err(e) => {
+            report.errors.push(e.to_string());
+            return report;
+        }
+    };
+    let result = sandbox
+        .capture_console()
+        .and_then(|_| sandbox.load(request.script.as_str()))
+        .and_then(|_| match request.kind {
+            ScriptKind::Parse => sandbox.call("main", request.payload.as_str()),
+            ScriptKind::Alarm => sandbox.call_json("main", request.payload.as_str()),
+        });
+    report.console = sandbox.take_console().unwrap_or_default();
+
+    let output = match result {
+        Ok(output) => output,
+        Err(e) => {
+            report.errors.push(e.to_string());
+            return report;
+        }
+    };
+    report.output = Some(output.clone());
+
+    match request.kind {
+        ScriptKind::Parse => check_parse_output(request, output.as_str(), redis, &mut report),
+        ScriptKind::Alarm => {
+            if output != "true" && output != "false" {
+                report
+                    .errors
+                    .push(format!("报警脚本应返回 true 或 false, 实际返回 {}", output));
+            }
+        }
+    }
+    report.ok = report.errors.is_empty();
+    report
+}
+
+fn check_parse_output(
+    request: &DryRunRequest,
+    output: &str,
+    redis: Option<&RedisOp>,
+    report: &mut DryRunReport,
+) {
+    let mut rows: Vec<DataRowList> = match serde_json::from_str(output) {
+        Ok(rows) => rows,
+        Err(e) => {
+            report
+                .errors
+                .push(format!("返回值不是 DataRowList 数组: {}", e));
+            return;
+        }
+    };
+    let protocol = request
+        .protocol
+        .clone()
+        .unwrap_or_else(|| "MQTT".to_string());
+    for row in rows.iter_mut() {
+        row.Protocol = Some(protocol.clone());
+        if row.DeviceUid.parse::<u32>().is_err() {
+            report
+                .errors
+                .push(format!("DeviceUid 不是有效的数字: {}", row.DeviceUid));
+        }
+    }
+
+    if let Some(redis) = redis {
+        for row in &rows {
+            match get_signals(
+                row.DeviceUid.as_str(),
+                row.IdentificationCode.as_str(),
+                redis,
+            ) {
+                Ok(signals) => report.signals.extend(check_signals(row, &signals)),
+                Err(e) => report.errors.push(format!("读取信号配置失败: {}", e)),
+            }
+        }
+    }
+    report.rows = rows;
+}
+
+/// 对照信号配置检查数据行, 与 storage_data_row 的入库规则一致
+pub fn check_signals(row: &DataRowList, signals: &[Signal]) -> Vec<SignalCheck> {
+    let by_name: HashMap<&str, &Signal> = signals.iter().map(|s| (s.name.as_str(), s)).collect();
+    let check = |name: &str, value: Option<&str>, signal_id, status| SignalCheck {
+        device_uid: row.DeviceUid.clone(),
+        identification_code: row.IdentificationCode.clone(),
+        name: name.to_string(),
+        value: value.map(|v| v.to_string()),
+        signal_id,
+        status,
+    };
+
+    let mut checks = Vec::new();
+    for data_row in &row.DataRows {
+        let text = data_row.Value.to_string();
+        let value = Some(text.as_str());
+        match by_name.get(data_row.Name.as_str()) {
+            Some(signal) => {
+                let status = if data_row.Value.coerce(signal.value_type()).is_none() {
+                    SignalCheckStatus::InvalidValue
+                } else {
+                    SignalCheckStatus::Matched
+                };
+                checks.push(check(&data_row.Name, value, Some(signal.id), status));
+            }
+            None => checks.push(check(
+                &data_row.Name,
+                value,
+                None,
+                SignalCheckStatus::Unmapped,
+            )),
+        }
+    }
+    for signal in signals {
+        if !row.DataRows.iter().any(|r| r.Name == signal.name) {
+            checks.push(check(
+                &signal.name,
+                None,
+                Some(signal.id),
+                SignalCheckStatus::Missing,
+            ));
+        }
+    }
+    checks
+}
+
+fn get_signals(
+    device_uid: &str,
+    identification_code: &str,
+    redis: &RedisOp,
+) -> Result<Vec<Signal>, Box<dyn Error>> {
+    let key = format!("signal:{}:{}", device_uid, identification_code);
+    let mut signals = Vec::new();
+    for value in redis.get_list_all(key.as_str())? {
+        signals.push(serde_json::from_str(value.as_str())?);
+    }
+    Ok(signals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARSE_SCRIPT: &str = r#"
+        function main(message) {
+            var data = JSON.parse(message);
+            console.log("temp", data.t);
+            return [{
+                "Time": 1730000000,
+                "DeviceUid": "1",
+                "IdentificationCode": "2",
+                "DataRows": [
+                    { "Name": "Temperature", "Value": String(data.t) },
+                    { "Name": "Status", "Value": "on" },
+                    { "Name": "Unknown", "Value": "1" }
+                ],
+                "Nc": ""
+            }];
+        }
+    "#;
+
+    fn request(kind: ScriptKind, script: &str, payload: &str) -> DryRunRequest {
+        DryRunRequest {
+            kind,
+            script: script.to_string(),
+            payload: payload.to_string(),
+            protocol: Some("TCP".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_dry_run_parse() {
+        let report = dry_run(
+            &request(ScriptKind::Parse, PARSE_SCRIPT, r#"{"t":23.5}"#),
+            None,
+        );
+        assert!(report.ok, "{:?}", report.errors);
+        assert_eq!(report.rows.len(), 1);
+        assert_eq!(report.rows[0].Protocol.as_deref(), Some("TCP"));
+        assert_eq!(report.console, vec!["[log] temp 23.5"]);
+
+        let report = dry_run(&request(ScriptKind::Parse, PARSE_SCRIPT, "{"), None);
+        assert!(!report.ok);
+        assert!(report.rows.is_empty());
+
+        let report = dry_run(
+            &request(ScriptKind::Parse, "function main() { return [{}]; }", ""),
+            None,
+        );
+        assert!(!report.ok);
+        assert!(report.errors[0].contains("DataRowList"));
+    }
+
+    #[test]
+    fn test_dry_run_alarm() {
+        let script = "function main(param) { return param.test.length > 0; }";
+        let report = dry_run(&request(ScriptKind::Alarm, script, r#"{"test":[1]}"#), None);
+        assert!(report.ok);
+        assert_eq!(report.output.as_deref(), Some("true"));
+
+        let report = dry_run(
+            &request(ScriptKind::Alarm, "function main() { return 1; }", "{}"),
+            None,
+        );
+        assert!(!report.ok);
+    }
+
+    #[test]
+    fn test_check_signals() {
+        let report = dry_run(
+            &request(ScriptKind::Parse, PARSE_SCRIPT, r#"{"t":"hot"}"#),
+            None,
+        );
+        let signals: Vec<Signal> = serde_json::from_str(
+            r#"[{"name":"Temperature","cache_size":0,"ID":5,"type":"数字"},
+                {"name":"Status","cache_size":0,"ID":6,"type":"文本"},
+                {"name":"Humidity","cache_size":0,"ID":7,"type":"数字"}]"#,
+        )
+        .unwrap();
+        let checks = check_signals(&report.rows[0], &signals);
+        let status: Vec<(&str, SignalCheckStatus)> =
+            checks.iter().map(|c| (c.name.as_str(), c.status)).collect();
+        assert_eq!(
+            status,
+            vec![
+                ("Temperature", SignalCheckStatus::InvalidValue),
+                ("Status", SignalCheckStatus::Matched),
+                ("Unknown", SignalCheckStatus::Unmapped),
+                ("Humidity", SignalCheckStatus::Missing),
+            ]
+        );
+    }
+}
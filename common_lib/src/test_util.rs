@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+
+pub type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+/// 简易 HTTP 服务, 记录每个请求的 (请求头, 请求体)
+///
+/// 依次使用 `statuses` 中的状态码响应, 用完后返回 200; `body` 不为空时作为 JSON 响应体返回
+pub async fn mock_server(statuses: Vec<u16>, body: &'static str) -> (String, Requests) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+
+    tokio::spawn(async move {
+        let mut statuses = statuses.into_iter();
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let mut tmp = [0u8; 4096];
+            let (head, request_body) = loop {
+                let n = stream.read(&mut tmp).await.unwrap();
+                if n == 0 {
+                    break (String::new(), String::new());
+                }
+                buf.extend_from_slice(&tmp[..n]);
+                let text = String::from_utf8_lossy(&buf).to_string();
+                if let Some(pos) = text.find("\r\n\r\n") {
+                    let head = text[..pos].to_string();
+                    let length = head
+                        .lines()
+                        .find_map(|l| {
+                            l.to_ascii_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if buf.len() >= pos + 4 + length {
+                        let body = String::from_utf8_lossy(&buf[pos + 4..pos + 4 + length]);
+                        break (head, body.to_string());
+                    }
+                }
+            };
+            if head.is_empty() {
+                continue;
+            }
+            recorded.lock().await.push((head, request_body));
+            let status = statuses.next().unwrap_or(200);
+            let content_type = if body.is_empty() {
+                ""
+            } else {
+                "Content-Type: application/json\r\n"
+            };
+            let response = format!(
+                "HTTP/1.1 {} MOCK\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                content_type,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+
+    (format!("http://{}", addr), requests)
+}
@@ -0,0 +1,172 @@
+use crate::models::SignalTransform;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Temperature,
+    Length,
+    Mass,
+    Time,
+    Pressure,
+    Voltage,
+    Current,
+    Power,
+    Energy,
+    Speed,
+    Ratio,
+}
+
+/// 单位定义, 基准单位值 = value * factor + offset
+struct Unit {
+    dimension: Dimension,
+    factor: f64,
+    offset: f64,
+}
+
+fn linear(dimension: Dimension, factor: f64) -> Unit {
+    Unit {
+        dimension,
+        factor,
+        offset: 0.0,
+    }
+}
+
+fn lookup(name: &str) -> Option<Unit> {
+    use Dimension::*;
+    let u = match name.trim() {
+        // 温度以开尔文为基准
+        "K" => linear(Temperature, 1.0),
+        "C" | "°C" | "℃" | "celsius" => Unit {
+            dimension: Temperature,
+            factor: 1.0,
+            offset: 273.15,
+        },
+        "F" | "°F" | "℉" | "fahrenheit" => Unit {
+            dimension: Temperature,
+            factor: 5.0 / 9.0,
+            offset: 273.15 - 32.0 * 5.0 / 9.0,
+        },
+        "mm" => linear(Length, 0.001),
+        "cm" => linear(Length, 0.01),
+        "m" => linear(Length, 1.0),
+        "km" => linear(Length, 1000.0),
+        "in" => linear(Length, 0.0254),
+        "ft" => linear(Length, 0.3048),
+        "g" => linear(Mass, 0.001),
+        "kg" => linear(Mass, 1.0),
+        "t" => linear(Mass, 1000.0),
+        "lb" => linear(Mass, 0.45359237),
+        "ms" => linear(Time, 0.001),
+        "s" => linear(Time, 1.0),
+        "min" => linear(Time, 60.0),
+        "h" => linear(Time, 3600.0),
+        "Pa" => linear(Pressure, 1.0),
+        "hPa" => linear(Pressure, 100.0),
+        "kPa" => linear(Pressure, 1000.0),
+        "MPa" => linear(Pressure, 1_000_000.0),
+        "mbar" => linear(Pressure, 100.0),
+        "bar" => linear(Pressure, 100_000.0),
+        "psi" => linear(Pressure, 6894.757293168),
+        "mV" => linear(Voltage, 0.001),
+        "V" => linear(Voltage, 1.0),
+        "kV" => linear(Voltage, 1000.0),
+        "mA" => linear(Current, 0.001),
+        "A" => linear(Current, 1.0),
+        "W" => linear(Power, 1.0),
+        "kW" => linear(Power, 1000.0),
+        "MW" => linear(Power, 1_000_000.0),
+        "J" => linear(Energy, 1.0),
+        "kJ" => linear(Energy, 1000.0),
+        "Wh" => linear(Energy, 3600.0),
+        "kWh" => linear(Energy, 3_600_000.0),
+        "m/s" => linear(Speed, 1.0),
+        "km/h" => linear(Speed, 1000.0 / 3600.0),
+        "%" => linear(Ratio, 0.01),
+        "‰" => linear(Ratio, 0.001),
+        _ => return None,
+    };
+    Some(u)
+}
+
+/// 在同一量纲的单位之间换算, 单位未知或量纲不同时返回错误
+pub fn convert_unit(value: f64, from: &str, to: &str) -> Result<f64, String> {
+    if from.trim() == to.trim() {
+        return Ok(value);
+    }
+    let source = lookup(from).ok_or_else(|| format!("未知单位: {}", from))?;
+    let target = lookup(to).ok_or_else(|| format!("未知单位: {}", to))?;
+    if source.dimension != target.dimension {
+        return Err(format!("单位 {} 无法换算为 {}", from, to));
+    }
+    let base = value * source.factor + source.offset;
+    Ok((base - target.offset) / target.factor)
+}
+
+/// 依次执行线性变换 value * scale + offset, 单位换算 source_unit -> unit 和范围限制
+pub fn apply_transform(
+    value: f64,
+    transform: &SignalTransform,
+    unit: Option<&str>,
+) -> Result<f64, String> {
+    let mut value = value * transform.scale.unwrap_or(1.0) + transform.offset.unwrap_or(0.0);
+    if let (Some(from), Some(to)) = (transform.source_unit.as_deref(), unit) {
+        value = convert_unit(value, from, to)?;
+    }
+    if let Some(min) = transform.min {
+        value = value.max(min);
+    }
+    if let Some(max) = transform.max {
+        value = value.min(max);
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_convert_unit() {
+        assert_close(convert_unit(212.0, "°F", "℃").unwrap(), 100.0);
+        assert_close(convert_unit(0.0, "C", "K").unwrap(), 273.15);
+        assert_close(convert_unit(-40.0, "C", "F").unwrap(), -40.0);
+        assert_close(convert_unit(1.5, "kWh", "Wh").unwrap(), 1500.0);
+        assert_close(convert_unit(1.0, "bar", "kPa").unwrap(), 100.0);
+        assert_close(convert_unit(36.0, "km/h", "m/s").unwrap(), 10.0);
+        assert_close(convert_unit(5.0, "V", "V").unwrap(), 5.0);
+
+        assert!(convert_unit(1.0, "V", "A").is_err());
+        assert!(convert_unit(1.0, "furlong", "m").is_err());
+    }
+
+    #[test]
+    fn test_apply_transform() {
+        // 12 位 ADC 计数换算为 0-100 ℃
+        let adc = SignalTransform {
+            scale: Some(100.0 / 4095.0),
+            min: Some(0.0),
+            max: Some(100.0),
+            ..Default::default()
+        };
+        assert_close(apply_transform(4095.0, &adc, None).unwrap(), 100.0);
+        assert_close(apply_transform(5000.0, &adc, None).unwrap(), 100.0);
+
+        let fahrenheit = SignalTransform {
+            offset: Some(-0.5),
+            source_unit: Some("F".to_string()),
+            ..Default::default()
+        };
+        assert_close(apply_transform(32.5, &fahrenheit, Some("C")).unwrap(), 0.0);
+        // 信号未配置单位时不换算
+        assert_close(apply_transform(32.5, &fahrenheit, None).unwrap(), 32.0);
+        assert!(apply_transform(1.0, &fahrenheit, Some("kg")).is_err());
+    }
+}
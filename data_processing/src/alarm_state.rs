@@ -0,0 +1,196 @@
+use common_lib::alarm_utils::{alarm_record_id, insert_alarm_record, resolve_alarm_record};
+use common_lib::models::{AlarmEvent, AlarmRecord, AlarmRuleType, AlarmState, AlarmStatus};
+use common_lib::mongo_utils::MongoDBManager;
+use common_lib::redis_pool_utils::RedisOp;
+use log::debug;
+use std::error::Error;
+
+/// 报警状态键, 脚本报警没有信号, signal_id 传 0
+pub fn alarm_state_key(
+    rule_type: AlarmRuleType,
+    rule_id: i32,
+    device_uid: &str,
+    identification_code: &str,
+    signal_id: i32,
+) -> String {
+    format!(
+        "alarm_state:{}:{}:{}:{}:{}",
+        rule_type.as_str(),
+        rule_id,
+        device_uid,
+        identification_code,
+        signal_id
+    )
+}
+
+/// 根据上一状态和本次评估结果计算新状态
+///
+/// 返回 None 表示状态不变, 否则返回 (新状态, 是否发生 FIRING/RESOLVED 迁移)
+pub fn next_state(prev: Option<&AlarmState>, hit: bool, time: i64) -> Option<(AlarmState, bool)> {
+    match prev {
+        // 乱序到达的旧数据不参与评估
+        Some(state) if time < state.last_time => None,
+        Some(state) if state.status == AlarmStatus::Firing => {
+            if hit {
+                // 报警中重复命中, 只更新计数, 不产生记录
+                let mut state = state.clone();
+                state.last_time = time;
+                state.count += 1;
+                Some((state, false))
+            } else {
+                let mut state = state.clone();
+                state.status = AlarmStatus::Resolved;
+                state.last_time = time;
+                state.resolved_at = Some(time);
+                Some((state, true))
+            }
+        }
+        _ if hit => Some((
+            AlarmState {
+                status: AlarmStatus::Firing,
+                fired_at: time,
+                last_time: time,
+                count: 1,
+                resolved_at: None,
+            },
+            true,
+        )),
+        _ => None,
+    }
+}
+
+pub fn get_alarm_state(key: &str, redis: &RedisOp) -> Result<Option<AlarmState>, Box<dyn Error>> {
+    match redis.get_string(key)? {
+        Some(value) => Ok(serde_json::from_str(&value).ok()),
+        None => Ok(None),
+    }
+}
+
+/// 待提交的报警状态迁移, previous 为计算时读取到的原值
+#[derive(Debug, Clone)]
+pub struct AlarmStateUpdate {
+    pub state: AlarmState,
+    previous: Option<String>,
+}
+
+/// 计算报警状态, 发生 FIRING/RESOLVED 迁移时返回待提交的更新
+///
+/// 重复命中等不产生迁移的更新直接写入. 迁移在报警记录写入并发布成功后由 commit_alarm_state 提交,
+/// 处理失败的消息重新投递时仍会得到同一迁移
+pub fn prepare_alarm_state(
+    key: &str,
+    hit: bool,
+    time: i64,
+    redis: &RedisOp,
+) -> Result<Option<AlarmStateUpdate>, Box<dyn Error>> {
+    let current = redis.get_string(key)?;
+    let prev: Option<AlarmState> = current
+        .as_deref()
+        .and_then(|value| serde_json::from_str(value).ok());
+
+    let (state, transition) = match next_state(prev.as_ref(), hit, time) {
+        Some(next) => next,
+        None => return Ok(None),
+    };
+    let update = AlarmStateUpdate {
+        state,
+        previous: current,
+    };
+    if transition {
+        return Ok(Some(update));
+    }
+    commit_alarm_state(key, &update, redis)?;
+    Ok(None)
+}
+
+/// 提交报警状态, 期间被其他消费者修改时不覆盖并返回 false
+pub fn commit_alarm_state(
+    key: &str,
+    update: &AlarmStateUpdate,
+    redis: &RedisOp,
+) -> Result<bool, Box<dyn Error>> {
+    let value = serde_json::to_string(&update.state)?;
+    let committed = redis.compare_and_set(key, update.previous.as_deref(), value.as_str())?;
+    if committed {
+        debug!("alarm state {} -> {:?}", key, update.state.status);
+    } else {
+        debug!("alarm state {} changed concurrently, skip", key);
+    }
+    Ok(committed)
+}
+
+/// 记录报警状态迁移: FIRING 新建报警记录, RESOLVED 更新恢复时间, 并回填 event.alarm_id
+pub async fn record_alarm_transition(
+    state_key: &str,
+    state: &AlarmState,
+    event: &mut AlarmEvent,
+    mongo_dbmanager: &MongoDBManager,
+) -> Result<(), Box<dyn Error>> {
+    let id = alarm_record_id(state_key, state.fired_at);
+    match state.status {
+        AlarmStatus::Firing => {
+            let record = AlarmRecord::from_event(id.clone(), event);
+            insert_alarm_record(mongo_dbmanager, &record)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        AlarmStatus::Resolved => {
+            let resolved_at = state.resolved_at.unwrap_or(state.last_time);
+            resolve_alarm_record(mongo_dbmanager, id.as_str(), resolved_at, state.duration())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    event.alarm_id = Some(id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_state_transitions() {
+        // 未报警且未命中: 无状态
+        assert!(next_state(None, false, 100).is_none());
+
+        // 触发
+        let (firing, transition) = next_state(None, true, 100).unwrap();
+        assert!(transition);
+        assert_eq!(firing.status, AlarmStatus::Firing);
+        assert_eq!(firing.fired_at, 100);
+
+        // 重复命中去重
+        let (firing, transition) = next_state(Some(&firing), true, 110).unwrap();
+        assert!(!transition);
+        assert_eq!(firing.count, 2);
+        assert_eq!(firing.fired_at, 100);
+
+        // 乱序数据忽略
+        assert!(next_state(Some(&firing), false, 105).is_none());
+
+        // 恢复
+        let (resolved, transition) = next_state(Some(&firing), false, 160).unwrap();
+        assert!(transition);
+        assert_eq!(resolved.status, AlarmStatus::Resolved);
+        assert_eq!(resolved.duration(), 60);
+
+        // 已恢复且未命中: 状态不变
+        assert!(next_state(Some(&resolved), false, 170).is_none());
+
+        // 再次触发
+        let (firing, transition) = next_state(Some(&resolved), true, 200).unwrap();
+        assert!(transition);
+        assert_eq!(firing.fired_at, 200);
+        assert_eq!(firing.count, 1);
+        assert!(firing.resolved_at.is_none());
+    }
+
+    #[test]
+    fn test_alarm_state_key() {
+        assert_eq!(
+            alarm_state_key(AlarmRuleType::Threshold, 3, "1", "2", 5),
+            "alarm_state:threshold:3:1:2:5"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use common_lib::config::read_config_tb;
+use common_lib::redis_pool_utils::{create_redis_pool_from_config, RedisOp};
+use common_lib::script_dry_run::{dry_run, DryRunRequest, ScriptKind};
+use std::error::Error;
+use std::fs;
+use std::io::Read;
+use std::process::exit;
+
+const USAGE: &str =
+    "用法: script_dry_run <脚本文件> <报文文件|-> [--kind parse|alarm] [--protocol MQTT] [--redis]
+
+  报文文件为 - 时从标准输入读取
+  --redis  读取 app-local.yml 中的 Redis 配置, 检查信号配置是否匹配";
+
+fn parse_args(args: &[String]) -> Result<(DryRunRequest, bool), Box<dyn Error>> {
+    let mut positional = Vec::new();
+    let mut kind = ScriptKind::Parse;
+    let mut protocol = None;
+    let mut use_redis = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--kind" => {
+                kind = match iter.next().map(|s| s.as_str()) {
+                    Some("parse") => ScriptKind::Parse,
+                    Some("alarm") => ScriptKind::Alarm,
+                    other => return Err(format!("未知的脚本类型: {:?}", other).into()),
+                }
+            }
+            "--protocol" => protocol = Some(iter.next().ok_or("--protocol 缺少参数")?.clone()),
+            "--redis" => use_redis = true,
+            _ => positional.push(arg.clone()),
+        }
+    }
+    if positional.len() != 2 {
+        return Err(USAGE.into());
+    }
+
+    let script = fs::read_to_string(&positional[0])?;
+    let payload = if positional[1] == "-" {
+        let mut payload = String::new();
+        std::io::stdin().read_to_string(&mut payload)?;
+        payload
+    } else {
+        fs::read_to_string(&positional[1])?
+    };
+    Ok((
+        DryRunRequest {
+            kind,
+            script,
+            payload,
+            protocol,
+        },
+        use_redis,
+    ))
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (request, use_redis) = match parse_args(&args) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(2);
+        }
+    };
+
+    let redis = if use_redis {
+        let config = read_config_tb("app-local.yml");
+        Some(RedisOp {
+            pool: create_redis_pool_from_config(&config.redis_config),
+        })
+    } else {
+        None
+    };
+
+    let report = dry_run(&request, redis.as_ref());
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    if !report.ok {
+        exit(1);
+    }
+}
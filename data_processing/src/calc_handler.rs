@@ -1,21 +1,19 @@
 use crate::storage_handler::{calc_bucket_name, calc_measurement};
 use bson::{Bson, Document};
-use chrono::Utc;
+use common_lib::flux_query::{Aggregate, FluxQuery, TimeRange};
 use common_lib::influxdb_utils::InfluxDBManager;
-use common_lib::models::{AggregationConfig, CalcCache, InfluxQueryConfig};
+use common_lib::js_pool::call_cached_json;
+use common_lib::models::{CalcCache, SignalValue};
 use common_lib::mongo_utils::MongoDBManager;
 use common_lib::redis_handler::RedisWrapper;
-use cron::Schedule;
 use futures_util::StreamExt;
 use influxdb2_structmap::value::Value;
 use log::{debug, error, info, trace};
-use quick_js::{Context, JsValue};
 use serde::de::{self, MapAccess, Visitor};
 use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{from_str, Error};
 use std::collections::HashMap;
 use std::fmt;
-use std::str::FromStr;
 
 use crate::waring_dealy_handler::handler_waring_delay_string;
 use crate::waring_handler::calc_collection_name;
@@ -28,8 +26,8 @@ use lapin::{Channel, Connection};
 
 #[derive(Debug)]
 enum LocValue {
-    Map(HashMap<i64, f64>),
-    Scalar(f64),
+    Map(HashMap<i64, SignalValue>),
+    Scalar(SignalValue),
 }
 
 impl Serialize for LocValue {
@@ -45,7 +43,7 @@ impl Serialize for LocValue {
                 }
                 ser_map.end()
             }
-            LocValue::Scalar(scalar) => serializer.serialize_f64(*scalar),
+            LocValue::Scalar(scalar) => scalar.serialize(serializer),
         }
     }
 }
@@ -68,7 +66,7 @@ impl<'de> Deserialize<'de> for LocValue {
             where
                 E: de::Error,
             {
-                Ok(LocValue::Scalar(value))
+                Ok(LocValue::Scalar(SignalValue::Float(value)))
             }
 
             fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
@@ -102,7 +100,7 @@ pub async fn calc_handler(
 
     match my_map {
         Ok(ref map) => {
-            if let Some(id_value) = my_map.expect("REASON").get("id") {
+            if let Some(id_value) = map.get("id") {
                 let id_str = id_value.to_string(); // 将 i64 转换为 String
                 info!("id as string: {}", id_str);
                 let influxdb = InfluxDBManager::new(host, port, org, token);
@@ -114,14 +112,11 @@ pub async fn calc_handler(
                 } else if let Some(result) = option {
                     let ccc_res: Result<CalcCache, serde_json::Error> = from_str(result.as_str());
                     if let Ok(ccc) = ccc_res {
-                        let next_time = get_next_time(ccc.cron.as_str()).unwrap();
-
-                        info!("next time: {}", next_time);
-
-                        let k = format!("calc_queue_param:{}", id_value);
-
-                        info!("k: {}", k);
-                        let value = redis_wrapper.get_string(k.as_str()).unwrap();
+                        // 调度器发送的任务携带本次执行时间, 只有 id 的旧消息无法确定时间窗口, 跳过
+                        let value = map.get("time").map(|time| time.to_string());
+                        if value.is_none() {
+                            error!("计算任务 {} 缺少执行时间, 跳过", id_str);
+                        }
                         if value.is_some() {
                             match value.unwrap().parse::<i64>() {
                                 Ok(pre_time) => {
@@ -138,28 +133,35 @@ pub async fn calc_handler(
                                             cache.protocol.as_str(),
                                         );
                                         if "原始" == cache.reduce.as_str() {
-                                            let influxdb_config = InfluxQueryConfig {
-                                                bucket: bkn,
-                                                measurement: mm,
-                                                fields: vec![cache.signal_id.to_string()],
-                                                start_time: pre_time - ccc.offset,
-                                                end_time: pre_time,
-                                                aggregation: AggregationConfig {
-                                                    every: 1,
-                                                    function: "mean".to_string(),
-                                                    create_empty: false,
+                                            let query_string = FluxQuery::new(
+                                                bkn.as_str(),
+                                                TimeRange::Absolute {
+                                                    start: pre_time - ccc.offset,
+                                                    stop: pre_time,
                                                 },
-                                                reduce: cache.reduce,
+                                            )
+                                            .measurement(mm.as_str())
+                                            .field(cache.signal_id.to_string().as_str())
+                                            .window(1, false)
+                                            .aggregate(Aggregate::Mean)
+                                            .build();
+                                            let query_string = match query_string {
+                                                Ok(query_string) => query_string,
+                                                Err(e) => {
+                                                    error!(
+                                                        "计算规则 {} 参数 {}: {}",
+                                                        id_str, cache.name, e
+                                                    );
+                                                    continue;
+                                                }
                                             };
-                                            let query_string =
-                                                influxdb_config.generate_flux_query();
 
                                             let vec1 = influxdb
                                                 .query_with_string(query_string)
                                                 .await
                                                 .unwrap();
 
-                                            let mut v: HashMap<i64, f64> = HashMap::new();
+                                            let mut v: HashMap<i64, SignalValue> = HashMap::new();
                                             if vec1.is_empty() {
                                                 info!("no data");
                                             } else {
@@ -167,20 +169,10 @@ pub async fn calc_handler(
                                                     // 打印每条记录的详细信息
 
                                                     let va = record.values.get("_value").unwrap();
-                                                    let mut vaa: f64 = 0.0;
-                                                    match va {
-                                                        Value::Unknown => {}
-                                                        Value::String(_) => {}
-                                                        Value::Double(fa) => {
-                                                            vaa = fa.0;
-                                                        }
-                                                        Value::Bool(_) => {}
-                                                        Value::Long(_) => {}
-                                                        Value::UnsignedLong(_) => {}
-                                                        Value::Duration(_) => {}
-                                                        Value::Base64Binary(_) => {}
-                                                        Value::TimeRFC(_) => {}
-                                                    }
+                                                    let vaa = match flux_signal_value(va) {
+                                                        Some(vaa) => vaa,
+                                                        None => continue,
+                                                    };
 
                                                     let time = record.values.get("_time").unwrap();
                                                     let mut t: i64 = 0;
@@ -207,21 +199,38 @@ pub async fn calc_handler(
                                                 m.insert(cache.name, LocValue::Map(v));
                                             }
                                         } else {
-                                            let influxdb_config = InfluxQueryConfig {
-                                                bucket: bkn,
-                                                measurement: mm,
-                                                fields: vec![cache.signal_id.to_string()],
-                                                start_time: pre_time - ccc.offset,
-                                                end_time: pre_time,
-                                                aggregation: AggregationConfig {
-                                                    every: 1,
-                                                    function: "mean".to_string(),
-                                                    create_empty: false,
+                                            let aggregate = match cache.reduce.parse::<Aggregate>()
+                                            {
+                                                Ok(aggregate) => aggregate,
+                                                Err(e) => {
+                                                    error!(
+                                                        "计算规则 {} 参数 {}: {}",
+                                                        id_str, cache.name, e
+                                                    );
+                                                    continue;
+                                                }
+                                            };
+                                            let query_string = FluxQuery::new(
+                                                bkn.as_str(),
+                                                TimeRange::Absolute {
+                                                    start: pre_time - ccc.offset,
+                                                    stop: pre_time,
                                                 },
-                                                reduce: cache.reduce,
+                                            )
+                                            .measurement(mm.as_str())
+                                            .field(cache.signal_id.to_string().as_str())
+                                            .aggregate(aggregate)
+                                            .build();
+                                            let query_string = match query_string {
+                                                Ok(query_string) => query_string,
+                                                Err(e) => {
+                                                    error!(
+                                                        "计算规则 {} 参数 {}: {}",
+                                                        id_str, cache.name, e
+                                                    );
+                                                    continue;
+                                                }
                                             };
-                                            let query_string =
-                                                influxdb_config.generate_flux_reduce();
                                             let vec1 = influxdb
                                                 .query_with_string(query_string)
                                                 .await
@@ -231,20 +240,10 @@ pub async fn calc_handler(
                                             } else {
                                                 for record in vec1 {
                                                     let va = record.values.get("_value").unwrap();
-                                                    let mut vaa: f64 = 0.0;
-                                                    match va {
-                                                        Value::Unknown => {}
-                                                        Value::String(_) => {}
-                                                        Value::Double(fa) => {
-                                                            vaa = fa.0;
-                                                        }
-                                                        Value::Bool(_) => {}
-                                                        Value::Long(_) => {}
-                                                        Value::UnsignedLong(_) => {}
-                                                        Value::Duration(_) => {}
-                                                        Value::Base64Binary(_) => {}
-                                                        Value::TimeRFC(_) => {}
-                                                    }
+                                                    let vaa = match flux_signal_value(va) {
+                                                        Some(vaa) => vaa,
+                                                        None => continue,
+                                                    };
 
                                                     m.insert(
                                                         cache.name.clone(),
@@ -258,29 +257,14 @@ pub async fn calc_handler(
                                     let pa = serde_json::to_string(&m).unwrap();
                                     trace!("m = {}", pa);
 
-                                    let context = Context::new().unwrap();
-
-                                    context.eval(ccc.script.as_str()).unwrap();
-
-                                    let js_code_2 = r#"
-        function main2(data) {
-            return JSON.parse(data);
-        }"#;
-                                    context.eval(js_code_2).unwrap();
-                                    let js_code_3 = r#"
-        function main3(data) {
-            return JSON.stringify(data);
-        }"#;
-                                    context.eval(js_code_3).unwrap();
-                                    let value2 =
-                                        context.call_function("main2", [pa.clone()]).unwrap();
-
-                                    let value = context.call_function("main", [value2]).unwrap();
-
-                                    let fff = context.call_function("main3", [value]).unwrap();
-
-                                    match fff {
-                                        JsValue::String(json_str) => {
+                                    let owner = format!("calc_cache:{}", id_str);
+                                    match call_cached_json(
+                                        &owner,
+                                        ccc.script.as_str(),
+                                        "main",
+                                        pa.as_str(),
+                                    ) {
+                                        Ok(json_str) => {
                                             let document1 =
                                                 json_str_to_document(&json_str).unwrap();
                                             trace!("document1 = {}", document1);
@@ -291,9 +275,9 @@ pub async fn calc_handler(
 
                                             let mut document = Document::new();
                                             document.insert("calc_rule_id", id_str);
-                                            document.insert("ex_time", next_time);
-                                            document.insert("start_time", next_time - ccc.offset);
-                                            document.insert("end_time", next_time);
+                                            document.insert("ex_time", pre_time);
+                                            document.insert("start_time", pre_time - ccc.offset);
+                                            document.insert("end_time", pre_time);
                                             document.insert("param", document3);
                                             document.insert("script", Bson::String(ccc.script));
                                             document.insert("result", document1);
@@ -308,25 +292,10 @@ pub async fn calc_handler(
                                                 mongo.db.collection::<Document>(string.as_str());
 
                                             collection.insert_one(&document.clone()).await.unwrap();
-
-                                            // msg
-
-                                            redis_wrapper
-                                                .set_string(
-                                                    k.as_str(),
-                                                    next_time.to_string().as_str(),
-                                                )
-                                                .unwrap();
-                                            redis_wrapper
-                                                .add_zset(
-                                                    "calc_queue",
-                                                    msg.as_str(),
-                                                    next_time as f64,
-                                                )
-                                                .unwrap();
+                                            // 下一次执行由 calc_scheduler 登记
                                         }
-                                        _ => {
-                                            error!("存储异常");
+                                        Err(e) => {
+                                            error!("计算脚本执行失败 {}: {}", id_str, e);
                                         }
                                     }
                                 }
@@ -347,6 +316,21 @@ pub async fn calc_handler(
     }
 }
 
+/// 查询结果的 _value 转换为计算脚本的参数, 保留布尔、整数和字符串类型
+fn flux_signal_value(value: &Value) -> Option<SignalValue> {
+    match value {
+        Value::Double(v) => Some(SignalValue::Float(v.0)),
+        Value::Long(v) => Some(SignalValue::Int(*v)),
+        Value::UnsignedLong(v) => Some(match i64::try_from(*v) {
+            Ok(v) => SignalValue::Int(v),
+            Err(_) => SignalValue::Float(*v as f64),
+        }),
+        Value::Bool(v) => Some(SignalValue::Bool(*v)),
+        Value::String(v) => Some(SignalValue::Text(v.clone())),
+        _ => None,
+    }
+}
+
 fn json_str_to_document(json_str: &str) -> Result<Document, Box<dyn std::error::Error>> {
     // 解析 JSON 字符串为 `serde_json::Value`
     let json_value: serde_json::Value = match serde_json::from_str(json_str) {
@@ -390,28 +374,9 @@ fn json_str_to_document(json_str: &str) -> Result<Document, Box<dyn std::error::
     }
 }
 
-fn get_next_time(cron_expr: &str) -> Option<i64> {
-    // 解析 cron 表达式
-    let schedule = Schedule::from_str(cron_expr).ok()?;
-
-    // 计算下一次执行时间
-    let time = Utc::now();
-
-    let beijing_time = time.with_timezone(&chrono::FixedOffset::east(8 * 3600));
-
-    println!("当前时间: {}", beijing_time);
-    if let Some(next_time) = schedule.after(&time).next() {
-        // 返回时间戳（秒）
-        Some(next_time.timestamp())
-    } else {
-        None
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::{NaiveDateTime, TimeZone, Utc};
     use common_lib::config::{get_config, read_config, read_config_tb};
     use common_lib::init_logger;
     use common_lib::mongo_utils::{get_mongo, init_mongo};
@@ -456,63 +421,6 @@ mod tests {
         )
         .await;
     }
-
-    #[test]
-    fn test_get_next_time() {
-        // 定义一个 cron 表达式 (例如每分钟执行一次)
-        let cron_expr = "0 0/2 * * * ?";
-
-        // 获取当前时间
-        let now = common_lib::time_utils::local_to_utc();
-
-        // 获取下一次执行时间
-        if let Some(next_time) = get_next_time(cron_expr) {
-            let next_time_naive = NaiveDateTime::from_timestamp(next_time, 0);
-            let utc_time = Utc.from_utc_datetime(&next_time_naive);
-
-            // 转换为北京时间（CST，UTC+8）
-            let beijing_time = utc_time.with_timezone(&chrono::FixedOffset::east(8 * 3600));
-
-            println!("当前时间: {}", now);
-            println!("下一次执行时间: {}", beijing_time);
-            println!("下一次执行时间: {}", beijing_time.timestamp());
-        } else {
-            panic!("无法计算下一次执行时间");
-        }
-    }
-
-    #[test]
-    fn test_invalid_cron_expr() {
-        // 测试无效的 cron 表达式
-        let cron_expr = "invalid cron expression";
-
-        // 检查返回值是否为 None
-        assert!(get_next_time(cron_expr).is_none());
-    }
-
-    #[test]
-    fn test_get_next_time_specific_case() {
-        // 测试一个具体的 cron 表达式，假设当前时间是 2024-11-05 12:00:00
-        let cron_expr = "0 0 12 * * *"; // 每天中午12点执行
-
-        // 设定一个固定的时间，模拟当前时间
-        let fixed_now = NaiveDateTime::parse_from_str("2024-11-05 11:59:00", "%Y-%m-%d %H:%M:%S")
-            .expect("时间解析失败");
-        let fixed_now = chrono::DateTime::<Utc>::from_utc(fixed_now, Utc);
-
-        // 获取下一次执行时间
-        if let Some(next_time) = get_next_time(cron_expr) {
-            let next_time_naive = NaiveDateTime::from_timestamp(next_time, 0);
-
-            // 断言下一次执行时间应该是 2024-11-05 12:00:00
-            let expected_next_time =
-                NaiveDateTime::parse_from_str("2024-11-05 12:00:00", "%Y-%m-%d %H:%M:%S")
-                    .expect("时间解析失败");
-            assert_eq!(next_time_naive, expected_next_time);
-        } else {
-            panic!("无法计算下一次执行时间");
-        }
-    }
 }
 
 pub async fn calc_handler_mq(
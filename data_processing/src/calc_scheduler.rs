@@ -0,0 +1,130 @@
+use common_lib::calc_schedule::{
+    due_runs, get_calc_cache, next_run, parse_schedule, queue_member_id, CALC_QUEUE,
+};
+use common_lib::redis_pool_utils::RedisOp;
+use common_lib::time_utils::local_to_utc;
+use lapin::options::BasicPublishOptions;
+use lapin::{BasicProperties, Channel, Connection};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::time::Duration;
+
+/// 调度器租约, 多个 data_processing 实例中只有持有者执行调度
+pub const CALC_SCHEDULER_LEADER: &str = "calc_scheduler_leader";
+
+/// 租约有效期, 持有者每个周期续期, 宕机后其他实例最多等待该时长接管
+const LEASE_TTL_SECS: u64 = 10;
+
+/// 调度周期
+const TICK_SECS: u64 = 1;
+
+/// 发送到 calc_queue 消息队列的执行任务, time 为本次计算的截止时间
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CalcTask {
+    pub id: u64,
+    pub time: i64,
+}
+
+/// 取出到期的计算规则发送到消息队列, 并登记下一次执行时间, 返回发送的任务数
+pub async fn schedule_once(
+    redis: &RedisOp,
+    channel: &Channel,
+    now: i64,
+) -> Result<usize, Box<dyn Error>> {
+    let mut published = 0;
+
+    for (member, score) in redis.get_zset_by_score(CALC_QUEUE, now as f64)? {
+        let id = match queue_member_id(&member) {
+            Some(id) => id,
+            None => {
+                error!("调度队列成员无效, 已移除: {}", member);
+                redis.delete_zset(CALC_QUEUE, member.as_str())?;
+                continue;
+            }
+        };
+        // 读取失败可能是 Redis 暂时不可用, 保留任务下个周期重试
+        let cache = match get_calc_cache(id, redis) {
+            Ok(cache) => cache,
+            Err(e) => {
+                error!("计算规则 {} 读取失败, 稍后重试: {}", id, e);
+                continue;
+            }
+        };
+        let schedule: Result<_, Box<dyn Error>> = match cache {
+            Some(cache) => {
+                parse_schedule(cache.cron.as_str()).map(|schedule| (schedule, cache.catch_up))
+            }
+            None => Err(format!("计算规则 {} 不存在", id).into()),
+        };
+        let (schedule, catch_up) = match schedule {
+            Ok(schedule) => schedule,
+            Err(e) => {
+                error!("计算规则 {} 无法调度, 已移出队列: {}", id, e);
+                redis.delete_zset(CALC_QUEUE, member.as_str())?;
+                continue;
+            }
+        };
+
+        let runs = due_runs(&schedule, score as i64, now, catch_up);
+        if runs.len() > 1 {
+            info!("计算规则 {} 补偿执行 {} 次", id, runs.len());
+        }
+        for time in runs {
+            let task = serde_json::to_string(&CalcTask { id, time })?;
+            channel
+                .basic_publish(
+                    "",
+                    CALC_QUEUE,
+                    BasicPublishOptions::default(),
+                    task.as_bytes(),
+                    BasicProperties::default(),
+                )
+                .await?;
+            published += 1;
+        }
+
+        match next_run(&schedule, now) {
+            Some(next) => {
+                debug!("计算规则 {} 下一次执行时间 {}", id, next);
+                redis.add_zset(CALC_QUEUE, member.as_str(), next as f64)?;
+            }
+            None => {
+                info!("计算规则 {} 没有后续执行时间, 已移出队列", id);
+                redis.delete_zset(CALC_QUEUE, member.as_str())?;
+            }
+        }
+    }
+
+    Ok(published)
+}
+
+pub async fn calc_scheduler(guard: &RedisOp, rabbit_conn: &Connection, node_name: &str) {
+    let channel = rabbit_conn.create_channel().await.unwrap();
+    let owner = format!("{}:{}", node_name, std::process::id());
+    let mut interval = tokio::time::interval(Duration::from_secs(TICK_SECS));
+    let mut leader = false;
+
+    info!("calc scheduler started, owner {}", owner);
+    loop {
+        interval.tick().await;
+        let acquired = match guard.acquire_lease(CALC_SCHEDULER_LEADER, &owner, LEASE_TTL_SECS) {
+            Ok(acquired) => acquired,
+            Err(e) => {
+                error!("调度器租约获取失败: {}", e);
+                false
+            }
+        };
+        if acquired != leader {
+            info!("calc scheduler {} leader: {}", owner, acquired);
+            leader = acquired;
+        }
+        if !leader {
+            continue;
+        }
+
+        if let Err(e) = schedule_once(guard, &channel, local_to_utc()).await {
+            error!("计算规则调度失败: {}", e);
+        }
+    }
+}
@@ -2,6 +2,7 @@ use crate::storage_handler::storage_data_row;
 use chrono::Utc;
 use common_lib::config::{get_config, Config, InfluxConfig};
 use common_lib::influxdb_utils::InfluxDBManager;
+use common_lib::js_pool::{call_cached, invalidate_cached};
 use common_lib::models::{CoapMessage, DataRowList, DataValue, MQTTMessage, Signal, SignalMapping};
 use common_lib::rabbit_utils::RabbitMQ;
 use common_lib::redis_handler::{get_redis_instance, RedisWrapper};
@@ -11,7 +12,6 @@ use lapin::options::{BasicAckOptions, BasicConsumeOptions, BasicPublishOptions};
 use lapin::types::FieldTable;
 use lapin::{BasicProperties, Channel, Connection};
 use log::{error, info};
-use quick_js::{Context, ContextError};
 use serde::{Deserialize, Serialize};
 use serde_json::from_str;
 use std::collections::HashMap;
@@ -21,7 +21,6 @@ use tokio::sync::{Mutex, MutexGuard};
 
 async fn handler_data_storage_string(
     result: String,
-    jsc: Context,
     config: InfluxConfig,
     redis: &RedisOp,
     rabbit_conn: &Connection,
@@ -35,49 +34,30 @@ async fn handler_data_storage_string(
         .get_hash("struct:Coap", mqtt_message.uid.as_str())
         .unwrap();
 
+    let owner = format!("struct:Coap:{}", mqtt_message.uid);
     if let Some(string) = option {
-        // 在这里创建 JavaScript 上下文
-        jsc.eval(&string)
-            .map_err(|e| Box::new(e) as Box<dyn Error>)?;
-
-        // 调用 main 函数
-        let value = jsc
-            .call_function("main", [mqtt_message.message])
-            .map_err(|e| Box::new(e) as Box<dyn Error>)?;
-
-        // 定义并调用 JavaScript 代码
-        let js_code_2 = r#"
-        function main2(data) {
-            return JSON.stringify(data);
-        }"#;
-        jsc.eval(js_code_2)
-            .map_err(|e| Box::new(e) as Box<dyn Error>)?;
-        let value2 = jsc
-            .call_function("main2", [value])
-            .map_err(|e| Box::new(e) as Box<dyn Error>)?;
-
-        let x = value2.as_str().unwrap_or("");
+        // 在缓存的沙箱中调用解析脚本的 main 函数, 结果为 JSON 字符串
+        let x = call_cached(&owner, &string, "main", mqtt_message.message.as_str())?;
 
         info!("Java Script Result = {:?}", x);
-        let dt: Vec<DataRowList> = from_str(&x).map_err(|e| Box::new(e) as Box<dyn Error>)?;
+        let mut dt: Vec<DataRowList> = from_str(&x).map_err(|e| Box::new(e) as Box<dyn Error>)?;
         info!("{:?}", dt);
-
-        // 存储数据行
-        for mut data_row in dt {
+        for data_row in dt.iter_mut() {
             data_row.Protocol = Some("COAP".to_string());
+        }
+        // 存储数据行, 数值信号按配置变换
+        for data_row in dt.iter_mut() {
             storage_data_row(
                 data_row,
                 "COAP",
-                config.host.clone().unwrap().as_str(),
-                config.port.clone().unwrap(),
-                config.org.clone().unwrap().as_str(),
-                config.token.clone().unwrap().as_str(),
                 config.bucket.clone().unwrap().as_str(),
                 redis,
             )
             .await
             .expect("storage_data_row error");
         }
+        // 下游消息携带协议字段和变换后的值
+        let message = serde_json::to_string(&dt)?;
 
         // 创建 RabbitMQ 通道
         let rabbit_channel = rabbit_conn
@@ -92,7 +72,7 @@ async fn handler_data_storage_string(
                     "",
                     *queue,
                     BasicPublishOptions::default(),
-                    x.clone().as_bytes(),
+                    message.as_bytes(),
                     BasicProperties::default(),
                 )
                 .await
@@ -101,6 +81,8 @@ async fn handler_data_storage_string(
 
         // fixme: 处理最后推送时间（如果需要的话）
     } else {
+        // 脚本已删除, 释放缓存的沙箱
+        invalidate_cached(&owner);
         info!("未找到脚本 for uid: {}", mqtt_message.uid);
     }
 
@@ -133,7 +115,6 @@ pub async fn pre_coap_handler(
 
                 match handler_data_storage_string(
                     result,
-                    Context::new().unwrap(),
                     guard1.influx_config.clone().unwrap(),
                     guard,
                     rabbit_conn,
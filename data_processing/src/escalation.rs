@@ -0,0 +1,366 @@
+use crate::silence::is_silenced;
+use crate::waring_handler::publish_alarm_event;
+use common_lib::alarm_utils::get_alarm;
+use common_lib::models::{
+    AlarmEvent, AlarmLifecycle, AlarmRecord, AlarmRuleType, AlarmStatus, EscalationPolicy,
+    EscalationTask,
+};
+use common_lib::mongo_utils::MongoDBManager;
+use common_lib::redis_pool_utils::RedisOp;
+use lapin::{Channel, Connection};
+use log::{debug, error, info};
+use std::error::Error;
+use std::time::Duration;
+
+/// 待执行的升级任务, 分值为执行时间(秒级时间戳)
+pub const ESCALATION_QUEUE: &str = "escalation_queue";
+
+/// 静默期间触发的报警, 成员为 level 0 的 EscalationTask, 分值为下一次检查时间
+///
+/// 静默结束时报警仍未处理则补发通知并开始升级
+pub const SILENCED_QUEUE: &str = "silenced_alarm_queue";
+
+/// 升级检查周期
+const CHECK_INTERVAL_SECS: u64 = 10;
+
+pub fn get_escalation_policy(
+    rule_type: AlarmRuleType,
+    rule_id: i32,
+    redis: &RedisOp,
+) -> Result<Option<EscalationPolicy>, Box<dyn Error>> {
+    let field = format!("{}:{}", rule_type.as_str(), rule_id);
+    match redis.get_hash("escalation_policy", field.as_str())? {
+        Some(value) => match serde_json::from_str::<EscalationPolicy>(&value) {
+            Ok(policy) => Ok(Some(policy)),
+            Err(e) => {
+                error!("升级策略反序列化失败 {}: {}", field, e);
+                Ok(None)
+            }
+        },
+        None => Ok(None),
+    }
+}
+
+/// 计算已通知 task.level 层级后的下一个升级任务及执行时间, None 表示升级结束
+///
+/// 未到最后一层时按下一层的 after_minutes 计算, 最后一层按 repeat_minutes 重复
+pub fn next_escalation(
+    policy: &EscalationPolicy,
+    task: &EscalationTask,
+    fired_at: i64,
+    now: i64,
+) -> Option<(EscalationTask, i64)> {
+    let level = task.level as usize;
+    if let Some(tier) = policy.tiers.get(level) {
+        let mut next = task.clone();
+        next.level += 1;
+        // 层级间隔配置过短时不在同一轮连续通知
+        return Some((next, (fired_at + tier.after_minutes * 60).max(now)));
+    }
+    if policy.repeat_minutes > 0 {
+        return Some((task.clone(), now + policy.repeat_minutes * 60));
+    }
+    None
+}
+
+/// 报警触发时登记第一层级升级任务, 静默期间触发的报警等静默结束后再检查
+pub fn schedule_escalation(event: &AlarmEvent, redis: &RedisOp) -> Result<(), Box<dyn Error>> {
+    if event.status != AlarmStatus::Firing {
+        return Ok(());
+    }
+    let alarm_id = match &event.alarm_id {
+        Some(alarm_id) => alarm_id,
+        None => return Ok(()),
+    };
+    let task = EscalationTask {
+        alarm_id: alarm_id.clone(),
+        rule_type: event.rule_type,
+        rule_id: event.rule_id,
+        level: 0,
+    };
+    if event.silenced {
+        debug!("silenced alarm {} deferred", alarm_id);
+        redis.add_zset(
+            SILENCED_QUEUE,
+            serde_json::to_string(&task)?.as_str(),
+            (event.insert_time + CHECK_INTERVAL_SECS as i64) as f64,
+        )?;
+        return Ok(());
+    }
+    let policy = match get_escalation_policy(event.rule_type, event.rule_id, redis)? {
+        Some(policy) => policy,
+        None => return Ok(()),
+    };
+    if let Some((task, due)) = next_escalation(&policy, &task, event.time, event.time) {
+        debug!(
+            "schedule escalation {} level {} at {}",
+            alarm_id, task.level, due
+        );
+        redis.add_zset(
+            ESCALATION_QUEUE,
+            serde_json::to_string(&task)?.as_str(),
+            due as f64,
+        )?;
+    }
+    Ok(())
+}
+
+/// 报警已确认、已关闭或已恢复时停止升级
+pub fn need_escalation(record: &AlarmRecord) -> bool {
+    record.lifecycle == AlarmLifecycle::Open && record.status == AlarmStatus::Firing
+}
+
+fn escalation_event(record: &AlarmRecord, level: u32, now: i64) -> AlarmEvent {
+    AlarmEvent {
+        rule_id: record.rule_id,
+        rule_type: record.rule_type,
+        device_uid: record.device_uid.clone(),
+        identification_code: record.identification_code.clone(),
+        protocol: None,
+        signal_id: record.signal_id,
+        signal_name: record.signal_name.clone(),
+        value: record.value,
+        param: None,
+        time: record.fired_at,
+        insert_time: now,
+        severity: record.severity,
+        status: record.status,
+        duration: Some(now - record.fired_at),
+        alarm_id: Some(record.id.clone()),
+        silenced: false,
+        escalation_level: level,
+    }
+}
+
+/// 已领取的任务处理失败后重新登记, 等待下个检查周期重试
+fn requeue_task(redis: &RedisOp, queue: &str, member: &str, now: i64) {
+    let due = (now + CHECK_INTERVAL_SECS as i64) as f64;
+    if let Err(e) = redis.add_zset(queue, member, due) {
+        error!("任务重新登记失败 {} {}: {}", queue, member, e);
+    }
+}
+
+pub async fn check_escalation_once(
+    redis: &RedisOp,
+    mongo_dbmanager: &MongoDBManager,
+    channel: &Channel,
+) -> Result<(), Box<dyn Error>> {
+    let now = common_lib::time_utils::local_to_utc();
+
+    for (member, _) in redis.get_zset_by_score(ESCALATION_QUEUE, now as f64)? {
+        // 多个实例同时检查时只有成功移除成员的实例执行该任务
+        if !redis.claim_zset(ESCALATION_QUEUE, member.as_str())? {
+            continue;
+        }
+
+        let task: EscalationTask = match serde_json::from_str(&member) {
+            Ok(task) => task,
+            Err(e) => {
+                error!("升级任务反序列化失败 {}: {}", member, e);
+                continue;
+            }
+        };
+        if let Err(e) = run_escalation_task(&task, now, redis, mongo_dbmanager, channel).await {
+            error!("报警 {} 升级任务失败, 稍后重试: {}", task.alarm_id, e);
+            requeue_task(redis, ESCALATION_QUEUE, member.as_str(), now);
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_escalation_task(
+    task: &EscalationTask,
+    now: i64,
+    redis: &RedisOp,
+    mongo_dbmanager: &MongoDBManager,
+    channel: &Channel,
+) -> Result<(), Box<dyn Error>> {
+    let record = match get_alarm(mongo_dbmanager, task.alarm_id.as_str())
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        Some(record) if need_escalation(&record) => record,
+        _ => {
+            info!("报警 {} 已处理, 停止升级", task.alarm_id);
+            return Ok(());
+        }
+    };
+    let policy = match get_escalation_policy(task.rule_type, task.rule_id, redis)? {
+        Some(policy) => policy,
+        None => return Ok(()),
+    };
+
+    let mut event = escalation_event(&record, task.level, now);
+    // 静默期间照常发布, 通知服务跳过静默事件, 升级计划不变
+    event.silenced = is_silenced(&event, now, redis)?;
+    info!(
+        "报警 {} 升级通知第 {} 层级, silenced = {}",
+        task.alarm_id, task.level, event.silenced
+    );
+    publish_alarm_event(channel, &event).await?;
+
+    if let Some((next, due)) = next_escalation(&policy, task, record.fired_at, now) {
+        redis.add_zset(
+            ESCALATION_QUEUE,
+            serde_json::to_string(&next)?.as_str(),
+            due as f64,
+        )?;
+    }
+    Ok(())
+}
+
+/// 检查静默期间触发的报警, 静默结束且仍在报警时补发通知并登记升级任务
+pub async fn check_silenced_once(
+    redis: &RedisOp,
+    mongo_dbmanager: &MongoDBManager,
+    channel: &Channel,
+) -> Result<(), Box<dyn Error>> {
+    let now = common_lib::time_utils::local_to_utc();
+
+    for (member, _) in redis.get_zset_by_score(SILENCED_QUEUE, now as f64)? {
+        if !redis.claim_zset(SILENCED_QUEUE, member.as_str())? {
+            continue;
+        }
+        let task: EscalationTask = match serde_json::from_str(&member) {
+            Ok(task) => task,
+            Err(e) => {
+                error!("静默报警任务反序列化失败 {}: {}", member, e);
+                continue;
+            }
+        };
+        match run_silenced_task(&task, now, redis, mongo_dbmanager, channel).await {
+            Ok(false) => {}
+            Ok(true) => requeue_task(redis, SILENCED_QUEUE, member.as_str(), now),
+            Err(e) => {
+                error!("静默报警 {} 检查失败, 稍后重试: {}", task.alarm_id, e);
+                requeue_task(redis, SILENCED_QUEUE, member.as_str(), now);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 仍在静默时返回 true, 由调用方延后一个检查周期再检查
+async fn run_silenced_task(
+    task: &EscalationTask,
+    now: i64,
+    redis: &RedisOp,
+    mongo_dbmanager: &MongoDBManager,
+    channel: &Channel,
+) -> Result<bool, Box<dyn Error>> {
+    let record = match get_alarm(mongo_dbmanager, task.alarm_id.as_str())
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        Some(record) if need_escalation(&record) => record,
+        _ => {
+            debug!("静默报警 {} 已处理", task.alarm_id);
+            return Ok(false);
+        }
+    };
+
+    let mut event = escalation_event(&record, 0, now);
+    event.silenced = is_silenced(&event, now, redis)?;
+    if event.silenced {
+        return Ok(true);
+    }
+    info!("报警 {} 静默结束仍未恢复, 补发通知", task.alarm_id);
+    publish_alarm_event(channel, &event).await?;
+    schedule_escalation(&event, redis)?;
+    Ok(false)
+}
+
+pub async fn escalation_checker(
+    guard: &RedisOp,
+    rabbit_conn: &Connection,
+    mongo_dbmanager: &MongoDBManager,
+) {
+    let channel = rabbit_conn.create_channel().await.unwrap();
+    let mut interval = tokio::time::interval(Duration::from_secs(CHECK_INTERVAL_SECS));
+
+    info!("escalation checker started");
+    loop {
+        interval.tick().await;
+        if let Err(e) = check_escalation_once(guard, mongo_dbmanager, &channel).await {
+            error!("报警升级检查失败: {}", e);
+        }
+        if let Err(e) = check_silenced_once(guard, mongo_dbmanager, &channel).await {
+            error!("静默报警检查失败: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(repeat_minutes: i64) -> EscalationPolicy {
+        serde_json::from_value(serde_json::json!({
+            "tiers": [
+                {"after_minutes": 5, "notice": [{"robot_type": "feishu", "robot_id": 1}]},
+                {"after_minutes": 15, "notice": [{"robot_type": "dingtalk", "robot_id": 2}]},
+            ],
+            "repeat_minutes": repeat_minutes,
+        }))
+        .unwrap()
+    }
+
+    fn task(level: u32) -> EscalationTask {
+        EscalationTask {
+            alarm_id: "alarm_state:threshold:3:1:2:5:1000".to_string(),
+            rule_type: AlarmRuleType::Threshold,
+            rule_id: 3,
+            level,
+        }
+    }
+
+    #[test]
+    fn test_next_escalation() {
+        let repeat = policy(30);
+        let no_repeat = policy(0);
+
+        // 报警触发后 5 分钟通知第一层级
+        assert_eq!(
+            next_escalation(&repeat, &task(0), 1000, 1000),
+            Some((task(1), 1300))
+        );
+        // 第一层级通知后, 触发 15 分钟时通知第二层级
+        assert_eq!(
+            next_escalation(&repeat, &task(1), 1000, 1310),
+            Some((task(2), 1900))
+        );
+        // 检查延迟时不早于当前时间
+        assert_eq!(
+            next_escalation(&repeat, &task(1), 1000, 2000),
+            Some((task(2), 2000))
+        );
+        // 最后一层级按间隔重复
+        assert_eq!(
+            next_escalation(&repeat, &task(2), 1000, 1910),
+            Some((task(2), 1910 + 1800))
+        );
+
+        // 未配置重复时升级结束
+        assert_eq!(next_escalation(&no_repeat, &task(2), 1000, 1910), None);
+    }
+
+    #[test]
+    fn test_need_escalation() {
+        let event: AlarmEvent = serde_json::from_str(
+            r#"{"rule_id":3,"rule_type":"threshold","device_uid":"1","identification_code":"2",
+                "signal_id":5,"time":1000,"insert_time":1000}"#,
+        )
+        .unwrap();
+        let mut record = AlarmRecord::from_event("1".to_string(), &event);
+        assert!(need_escalation(&record));
+
+        record.lifecycle = AlarmLifecycle::Acknowledged;
+        assert!(!need_escalation(&record));
+
+        record.lifecycle = AlarmLifecycle::Open;
+        record.status = AlarmStatus::Resolved;
+        assert!(!need_escalation(&record));
+    }
+}
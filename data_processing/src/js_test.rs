@@ -1,4 +1,4 @@
-use quick_js::Context;
+use common_lib::js_sandbox::call_script;
 #[cfg(test)]
 mod tests {
     use crate::js_test::test_js;
@@ -9,8 +9,6 @@ mod tests {
     }
 }
 pub async fn test_js() {
-    let context = Context::new().unwrap();
-
     let js_code = r#"
         function main(nc) {
             var dataRows = [
@@ -29,17 +27,8 @@ pub async fn test_js() {
         }
     "#;
 
-    context.eval(js_code).unwrap();
-
     let nc_value = "42";
 
-    let value = context.call_function("main", [nc_value]).unwrap();
-
-    let js_code_2 = r#"
-        function main2(data) {
-            return JSON.stringify(data);
-        }"#;
-    context.eval(js_code_2).unwrap();
-    let value2 = context.call_function("main2", [value]).unwrap();
-    println!("{:?}", value2.as_str().unwrap_or(""));
+    let value = call_script(js_code, "main", nc_value).unwrap();
+    println!("{:?}", value);
 }
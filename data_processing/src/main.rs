@@ -1,14 +1,22 @@
 use crate::calc_handler::calc_handler_mq;
+use crate::calc_scheduler::calc_scheduler;
 use crate::coap_handler::pre_coap_handler;
+use crate::escalation::escalation_checker;
 use crate::http_handler::pre_http_handler;
+use crate::offline_handler::offline_checker;
 use crate::storage_handler::pre_handler;
+use crate::tcp_handler::pre_tcp_handler;
+use crate::transmit_handler::transmit_handler;
 use crate::waring_dealy_handler::waring_delay_handler;
 use crate::waring_handler::waring_handler;
 use crate::ws_handler::pre_ws_handler;
 use common_lib::config::{get_config, read_config, read_config_tb, RedisConfig};
+use common_lib::influxdb_utils::{init_influx_writer, run_influx_writer};
 use common_lib::init_logger;
 use common_lib::mongo_utils::{get_mongo, init_mongo};
-use common_lib::rabbit_utils::{get_rabbitmq_instance, init_rabbitmq_with_config};
+use common_lib::rabbit_utils::{
+    declare_alarm_exchange, get_rabbitmq_instance, init_rabbitmq_with_config,
+};
 use common_lib::redis_handler::{get_redis_instance, init_redis};
 use common_lib::redis_pool_utils::{create_redis_pool_from_config, RedisOp};
 use futures_util::StreamExt;
@@ -16,11 +24,19 @@ use lapin::types::FieldTable;
 use lapin::{options::QueueDeclareOptions, Channel, Connection, ConnectionProperties};
 use std::error::Error;
 
+mod alarm_state;
 mod calc_handler;
+mod calc_scheduler;
 mod coap_handler;
+mod escalation;
 mod http_handler;
 mod js_test;
+mod offline_handler;
+mod silence;
 mod storage_handler;
+mod tcp_handler;
+mod transmit;
+mod transmit_handler;
 mod waring_dealy_handler;
 mod waring_handler;
 mod ws_handler;
@@ -45,6 +61,7 @@ async fn main() {
 
     let mongoConfig = guard1.mongo_config.clone().unwrap();
     let option = guard1.influx_config.clone().unwrap();
+    init_influx_writer(option.writer.clone()).unwrap();
 
     let mongo_manager_wrapper = get_mongo().await.unwrap();
     ensure_queue_exists(&channel, "calc_queue").await;
@@ -57,6 +74,7 @@ async fn main() {
     ensure_queue_exists(&channel, "pre_http_handler").await;
     ensure_queue_exists(&channel, "pre_ws_handler").await;
     ensure_queue_exists(&channel, "pre_coap_handler").await;
+    declare_alarm_exchange(&channel).await.unwrap();
 
     let url = format!(
         "amqp://{}:{}@{}:{}",
@@ -81,17 +99,25 @@ async fn main() {
     let redisOp = RedisOp { pool };
 
     let (
+        influx_writer_result,
         pre_result,
         pre_coap_handler,
         pre_http_handler,
+        pre_tcp_handler,
         pre_ws_handler,
         waring_result,
         waring_dealy_handler,
         calc_handler_mq,
+        calc_scheduler_result,
+        transmit_result,
+        offline_result,
+        escalation_result,
     ) = tokio::join!(
+        run_influx_writer(&option),
         pre_handler(&guard1, &redisOp, &connection, &channel1),
         pre_coap_handler(&guard1, &redisOp, &connection, &channel1),
         pre_http_handler(&guard1, &redisOp, &connection, &channel1),
+        pre_tcp_handler(&guard1, &redisOp, &connection, &channel1),
         pre_ws_handler(&guard1, &redisOp, &connection, &channel1),
         waring_handler(
             option.clone(),
@@ -116,7 +142,16 @@ async fn main() {
             &channel1,
             mongoConfig.collection.clone().unwrap(),
             &mongo_manager_wrapper
-        )
+        ),
+        calc_scheduler(&redisOp, &connection, guard1.node_info.name.as_str()),
+        transmit_handler(&redisOp, &channel1),
+        offline_checker(
+            &redisOp,
+            &connection,
+            &mongo_manager_wrapper,
+            guard1.node_info.name.as_str()
+        ),
+        escalation_checker(&redisOp, &connection, &mongo_manager_wrapper)
     );
 
     tokio::signal::ctrl_c()
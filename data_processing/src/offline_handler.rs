@@ -0,0 +1,192 @@
+use crate::alarm_state::{
+    alarm_state_key, commit_alarm_state, prepare_alarm_state, record_alarm_transition,
+};
+use crate::escalation::schedule_escalation;
+use crate::silence::is_silenced;
+use crate::waring_handler::publish_alarm_event;
+use common_lib::models::{AlarmEvent, AlarmRuleType, OfflineConfig};
+use common_lib::mongo_utils::MongoDBManager;
+use common_lib::redis_pool_utils::RedisOp;
+use lapin::{Channel, Connection};
+use log::{debug, error, info};
+use std::error::Error;
+use std::time::Duration;
+
+/// 离线检查周期
+const CHECK_INTERVAL_SECS: u64 = 30;
+
+/// 离线检查租约, 多个 data_processing 实例中只有持有者执行检查
+pub const OFFLINE_CHECKER_LEADER: &str = "offline_checker_leader";
+
+/// 租约有效期, 大于检查周期, 持有者每个周期续期
+const LEASE_TTL_SECS: u64 = CHECK_INTERVAL_SECS * 3;
+
+/// SCAN 每次检查的键数量
+const SCAN_COUNT: usize = 500;
+
+/// 解析 storage_time:{protocol}:{uid}:{code}, 返回 (protocol, uid, code)
+pub fn parse_storage_time_key(key: &str) -> Option<(&str, &str, &str)> {
+    let mut parts = key.splitn(4, ':');
+    if parts.next()? != "storage_time" {
+        return None;
+    }
+    let protocol = parts.next()?;
+    let device_uid = parts.next()?;
+    let identification_code = parts.next()?;
+    Some((protocol, device_uid, identification_code))
+}
+
+/// 获取设备的离线配置, 设备配置优先于产品配置
+pub fn get_offline_config(
+    protocol: &str,
+    device_uid: &str,
+    identification_code: &str,
+    redis: &RedisOp,
+) -> Result<Option<OfflineConfig>, Box<dyn Error>> {
+    let key = "offline_config";
+    let device = format!("{}:{}:{}", protocol, device_uid, identification_code);
+
+    let mut value = redis.get_hash(key, device.as_str())?;
+    if value.is_none() {
+        if let Some(product) = redis.get_hash("device_product", device.as_str())? {
+            value = redis.get_hash(key, format!("product:{}", product).as_str())?;
+        }
+    }
+
+    match value {
+        Some(value) => match serde_json::from_str::<OfflineConfig>(&value) {
+            Ok(config) if config.enable => Ok(Some(config)),
+            Ok(_) => Ok(None),
+            Err(e) => {
+                error!("离线配置反序列化失败 {}: {}", device, e);
+                Ok(None)
+            }
+        },
+        None => Ok(None),
+    }
+}
+
+pub async fn check_offline_once(
+    redis: &RedisOp,
+    mongo_dbmanager: &MongoDBManager,
+    channel: &Channel,
+) -> Result<(), Box<dyn Error>> {
+    let now = common_lib::time_utils::local_to_utc();
+
+    for key in redis.scan_keys("storage_time:*", SCAN_COUNT)? {
+        let (protocol, device_uid, identification_code) = match parse_storage_time_key(&key) {
+            Some(v) => v,
+            None => continue,
+        };
+        let config = match get_offline_config(protocol, device_uid, identification_code, redis)? {
+            Some(config) => config,
+            None => continue,
+        };
+        let last_time: i64 = match redis.get_string(key.as_str())? {
+            Some(v) => v.parse().unwrap_or_default(),
+            None => continue,
+        };
+
+        let silence = now - last_time;
+        let hit = silence > config.timeout_seconds;
+        debug!("{} silence = {}s, offline = {}", key, silence, hit);
+
+        // 数据恢复上报后下一轮检查自动恢复
+        let state_key = alarm_state_key(
+            AlarmRuleType::Offline,
+            config.id,
+            device_uid,
+            identification_code,
+            0,
+        );
+        let update = match prepare_alarm_state(state_key.as_str(), hit, now, redis)? {
+            Some(update) => update,
+            None => continue,
+        };
+        let state = &update.state;
+        info!("离线报警状态迁移 {:?} {}", state.status, key);
+
+        let mut event = AlarmEvent {
+            rule_id: config.id,
+            rule_type: AlarmRuleType::Offline,
+            device_uid: device_uid.to_string(),
+            identification_code: identification_code.to_string(),
+            protocol: Some(protocol.to_string()),
+            signal_id: None,
+            signal_name: None,
+            value: Some(silence as f64),
+            param: None,
+            time: now,
+            insert_time: now,
+            severity: config.severity,
+            status: state.status,
+            duration: state.resolved_at.map(|_| state.duration()),
+            alarm_id: None,
+            silenced: false,
+            escalation_level: 0,
+        };
+        // 命中静默规则的报警照常记录和发布, 由通知服务跳过
+        event.silenced = is_silenced(&event, now, redis)?;
+        record_alarm_transition(state_key.as_str(), state, &mut event, mongo_dbmanager).await?;
+        publish_alarm_event(channel, &event).await?;
+        schedule_escalation(&event, redis)?;
+        // 发布成功后再提交状态, 失败时下一轮检查重新处理该迁移
+        commit_alarm_state(state_key.as_str(), &update, redis)?;
+    }
+
+    Ok(())
+}
+
+pub async fn offline_checker(
+    guard: &RedisOp,
+    rabbit_conn: &Connection,
+    mongo_dbmanager: &MongoDBManager,
+    node_name: &str,
+) {
+    let channel = rabbit_conn.create_channel().await.unwrap();
+    let owner = format!("{}:{}", node_name, std::process::id());
+    let mut interval = tokio::time::interval(Duration::from_secs(CHECK_INTERVAL_SECS));
+    let mut leader = false;
+
+    info!("offline checker started, owner {}", owner);
+    loop {
+        interval.tick().await;
+        let acquired = match guard.acquire_lease(OFFLINE_CHECKER_LEADER, &owner, LEASE_TTL_SECS) {
+            Ok(acquired) => acquired,
+            Err(e) => {
+                error!("离线检查租约获取失败: {}", e);
+                false
+            }
+        };
+        if acquired != leader {
+            info!("offline checker {} leader: {}", owner, acquired);
+            leader = acquired;
+        }
+        if !leader {
+            continue;
+        }
+
+        if let Err(e) = check_offline_once(guard, mongo_dbmanager, &channel).await {
+            error!("离线检查失败: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_storage_time_key() {
+        assert_eq!(
+            parse_storage_time_key("storage_time:MQTT:1:2"),
+            Some(("MQTT", "1", "2"))
+        );
+        assert_eq!(
+            parse_storage_time_key("storage_time:HTTP:1:a:b"),
+            Some(("HTTP", "1", "a:b"))
+        );
+        assert_eq!(parse_storage_time_key("storage_time:MQTT:1"), None);
+        assert_eq!(parse_storage_time_key("calc_queue:MQTT:1:2"), None);
+    }
+}
@@ -0,0 +1,174 @@
+use chrono::DateTime;
+use common_lib::models::{AlarmEvent, SilenceRule};
+use common_lib::redis_pool_utils::RedisOp;
+use cron::Schedule;
+use log::{error, info};
+use std::error::Error;
+use std::str::FromStr;
+
+/// 静默规则是否匹配报警, in_group 判断设备是否属于设备组
+pub fn rule_matches(
+    rule: &SilenceRule,
+    event: &AlarmEvent,
+    in_group: impl Fn(&str, &str) -> bool,
+) -> bool {
+    if let Some(device_uid) = &rule.device_uid {
+        if device_uid != &event.device_uid {
+            return false;
+        }
+    }
+    if let Some(group) = &rule.group {
+        if !in_group(group.as_str(), event.device_uid.as_str()) {
+            return false;
+        }
+    }
+    if let Some(signal_id) = rule.signal_id {
+        if event.signal_id != Some(signal_id) {
+            return false;
+        }
+    }
+    if let Some(rule_id) = rule.rule_id {
+        if rule_id != event.rule_id {
+            return false;
+        }
+    }
+    if let Some(rule_type) = rule.rule_type {
+        if rule_type != event.rule_type {
+            return false;
+        }
+    }
+    true
+}
+
+/// 静默窗口是否生效
+pub fn window_active(rule: &SilenceRule, now: i64) -> bool {
+    if rule.start.map(|start| now < start).unwrap_or(false)
+        || rule.end.map(|end| now > end).unwrap_or(false)
+    {
+        return false;
+    }
+
+    match &rule.cron {
+        // 存在 (now - duration, now] 内的 cron 触发时间即在周期窗口内
+        Some(cron_expr) => {
+            let schedule = match Schedule::from_str(cron_expr) {
+                Ok(schedule) => schedule,
+                Err(e) => {
+                    error!("静默规则 {} cron 表达式错误: {}", rule.id, e);
+                    return false;
+                }
+            };
+            let duration = rule.duration_seconds.unwrap_or(0);
+            let window_start = match DateTime::from_timestamp(now - duration, 0) {
+                Some(t) => t,
+                None => return false,
+            };
+            schedule
+                .after(&window_start)
+                .next()
+                .map(|t| t.timestamp() <= now)
+                .unwrap_or(false)
+        }
+        None => true,
+    }
+}
+
+/// 报警是否命中静默规则 (silence_rule 列表)
+pub fn is_silenced(event: &AlarmEvent, now: i64, redis: &RedisOp) -> Result<bool, Box<dyn Error>> {
+    for value in redis.get_list_all("silence_rule")? {
+        let rule: SilenceRule = match serde_json::from_str(&value) {
+            Ok(rule) => rule,
+            Err(e) => {
+                error!("静默规则反序列化失败: {}", e);
+                continue;
+            }
+        };
+        if !window_active(&rule, now) {
+            continue;
+        }
+        let in_group = |group: &str, device_uid: &str| {
+            redis
+                .get_set(format!("device_group:{}", group).as_str())
+                .map(|members| members.iter().any(|m| m == device_uid))
+                .unwrap_or(false)
+        };
+        if rule_matches(&rule, event, in_group) {
+            info!(
+                "报警 {}:{} 设备 {} 命中静默规则 {}",
+                event.rule_type.as_str(),
+                event.rule_id,
+                event.device_uid,
+                rule.id
+            );
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_lib::models::AlarmRuleType;
+
+    fn silence_rule(json: &str) -> SilenceRule {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn alarm_event() -> AlarmEvent {
+        serde_json::from_str(
+            r#"{"rule_id":3,"rule_type":"threshold","device_uid":"1","identification_code":"2",
+                "signal_id":5,"time":0,"insert_time":0}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let event = alarm_event();
+        let no_group = |_: &str, _: &str| false;
+
+        assert!(rule_matches(&silence_rule(r#"{"ID":1}"#), &event, no_group));
+        assert!(rule_matches(
+            &silence_rule(r#"{"ID":1,"device_uid":"1","signal_id":5}"#),
+            &event,
+            no_group
+        ));
+        assert!(!rule_matches(
+            &silence_rule(r#"{"ID":1,"device_uid":"1","signal_id":6}"#),
+            &event,
+            no_group
+        ));
+        assert!(!rule_matches(
+            &silence_rule(r#"{"ID":1,"rule_id":3,"rule_type":"script"}"#),
+            &event,
+            no_group
+        ));
+
+        let group_rule = silence_rule(r#"{"ID":1,"group":"pump"}"#);
+        assert!(!rule_matches(&group_rule, &event, no_group));
+        assert!(rule_matches(&group_rule, &event, |g, d| g == "pump" && d == "1"));
+        assert_eq!(event.rule_type, AlarmRuleType::Threshold);
+    }
+
+    #[test]
+    fn test_window_active() {
+        let rule = silence_rule(r#"{"ID":1,"start":100,"end":200}"#);
+        assert!(!window_active(&rule, 99));
+        assert!(window_active(&rule, 150));
+        assert!(!window_active(&rule, 201));
+
+        // 每天 02:00 (UTC) 开始, 持续 1 小时; 1730080800 = 2024-10-28 02:00:00 UTC
+        let rule = silence_rule(r#"{"ID":1,"cron":"0 0 2 * * *","duration_seconds":3600}"#);
+        assert!(window_active(&rule, 1730080800));
+        assert!(window_active(&rule, 1730080800 + 1800));
+        assert!(!window_active(&rule, 1730080800 + 3601));
+        assert!(!window_active(&rule, 1730080800 - 1));
+
+        // 周期窗口同时受 start/end 限制
+        let rule = silence_rule(
+            r#"{"ID":1,"cron":"0 0 2 * * *","duration_seconds":3600,"end":1730000000}"#,
+        );
+        assert!(!window_active(&rule, 1730080800));
+    }
+}
@@ -1,16 +1,19 @@
 use chrono::Utc;
 use common_lib::config::{get_config, Config, InfluxConfig};
-use common_lib::influxdb_utils::InfluxDBManager;
-use common_lib::models::{DataRowList, DataValue, MQTTMessage, Signal, SignalMapping};
+use common_lib::influxdb_utils::{get_influx_writer, InfluxPoint};
+use common_lib::js_pool::{call_cached, invalidate_cached};
+use common_lib::models::{
+    DataRowList, DataValue, MQTTMessage, Signal, SignalMapping, SignalSamples, SignalValue, Tv,
+};
 use common_lib::rabbit_utils::RabbitMQ;
 use common_lib::redis_handler::{get_redis_instance, RedisWrapper};
 use common_lib::redis_pool_utils::RedisOp;
+use common_lib::unit_utils::apply_transform;
 use futures_util::StreamExt;
 use lapin::options::{BasicAckOptions, BasicConsumeOptions, BasicPublishOptions};
 use lapin::types::FieldTable;
 use lapin::{BasicProperties, Channel, Connection};
 use log::{error, info};
-use quick_js::{Context, ContextError};
 use serde::{Deserialize, Serialize};
 use serde_json::from_str;
 use std::collections::HashMap;
@@ -18,13 +21,10 @@ use std::error::Error;
 use std::sync::Arc;
 use tokio::sync::{Mutex, MutexGuard};
 
+/// 写入数据行, dt 中的信号值替换为按信号配置转换、变换后的值, 供下游报警和转发使用
 pub async fn storage_data_row(
-    dt: DataRowList,
+    dt: &mut DataRowList,
     protocol: &str,
-    host: &str,
-    port: u16,
-    org: &str,
-    token: &str,
     bucket_pre: &str,
     redis: &RedisOp,
 ) -> Result<(), Box<dyn std::error::Error>> {
@@ -53,8 +53,6 @@ pub async fn storage_data_row(
     let bucket_name = calc_bucket_name(bucket_pre, protocol, device_uid);
 
     info!("bucket_name: {}", bucket_name);
-    // 创建 InfluxDB 管理器
-    let db_manager = InfluxDBManager::new(host, port, org, token);
 
     let now = common_lib::time_utils::local_to_utc();
     let measur = calc_measurement(device_uid_string, iden_code, protocol);
@@ -72,9 +70,7 @@ pub async fn storage_data_row(
         DataValue::Integer(now_timestamp - push_time),
     );
 
-    for x in dt.DataRows {
-        let data_value = x.Value;
-
+    for x in dt.DataRows.iter_mut() {
         let x1 = match map.get(x.Name.as_str()) {
             Some(mapping) => mapping,
             None => {
@@ -83,18 +79,42 @@ pub async fn storage_data_row(
             }
         };
 
-        if x1.numb {
-            let float_num: f64 = match data_value.parse() {
-                Ok(num) => num,
-                Err(_) => {
-                    error!("Failed to parse string to float: {}", data_value);
-                    continue; // 或者处理解析错误
-                }
+        // 按信号配置的类型转换, 字符串报文在这里解析
+        let raw = match x.Value.coerce(x1.value_type) {
+            Some(value) => value,
+            None => {
+                error!(
+                    "Failed to convert value {} to {:?}: {}",
+                    x.Value, x1.value_type, x.Name
+                );
+                continue;
+            }
+        };
+        let value = match transform_value(&raw, x1) {
+            Ok(value) => value,
+            Err(e) => {
+                error!("Failed to transform value {}: {}", x.Name, e);
+                continue;
+            }
+        };
+        insert_dt.insert(x1.id.to_string(), value.to_data_value());
+        if x1.transform.as_ref().map(|t| t.keep_raw).unwrap_or(false) {
+            insert_dt.insert(format!("{}_raw", x1.id), raw.to_data_value());
+        }
+        x.Value = value.clone();
+        // 最近样本供差值/变化率报警使用, 与 cache_size 无关
+        if let Some(v) = value.as_f64() {
+            let sample = Tv {
+                time: push_time,
+                value: v,
             };
-            insert_dt.insert(x1.id.to_string(), DataValue::Float(float_num));
-        } else {
-            insert_dt.insert(x1.id.to_string(), DataValue::Text(data_value.clone()));
+            set_signal_sample(device_uid_string, iden_code, x1.id, sample, redis)?;
         }
+        // 历史缓存中布尔值按 1/0 保存, 供脚本报警读取
+        let data_value = match value {
+            SignalValue::Bool(v) => (v as i32).to_string(),
+            _ => value.to_string(),
+        };
 
         let key = format!(
             "signal_delay_warning:{}:{}:{}",
@@ -127,13 +147,16 @@ pub async fn storage_data_row(
 
     info!("measur.as_str() = {}", measur.as_str());
 
-    // 写入数据
-    if let Err(e) = db_manager
-        .write(insert_dt, measur.as_str(), bucket_name.as_str())
-        .await
-    {
+    // 加入共享写入器队列, 使用入库时的纳秒时间戳, 避免同一秒内的数据互相覆盖
+    let point = InfluxPoint {
+        measurement: measur,
+        tags: HashMap::new(),
+        fields: insert_dt,
+        timestamp: Utc::now().timestamp_nanos_opt(),
+    };
+    // 写入器队列已满时丢弃数据点, 这里只在写入器未初始化或已停止时报错
+    if let Err(e) = get_influx_writer().and_then(|w| w.write(bucket_name.as_str(), point)) {
         error!("Failed to write data to InfluxDB: {:?}", e);
-        return Err(e);
     }
 
     set_push_time(protocol, iden_code, device_uid_string, now_timestamp, redis);
@@ -141,6 +164,38 @@ pub async fn storage_data_row(
     Ok(())
 }
 
+/// 对数值信号执行线性变换、单位换算和范围限制
+///
+/// 配置了线性变换或单位换算时结果为浮点数, 整数信号只做范围限制时保持整数
+pub fn transform_value(
+    value: &SignalValue,
+    mapping: &SignalMapping,
+) -> Result<SignalValue, String> {
+    let transform = match &mapping.transform {
+        Some(transform) => transform,
+        None => return Ok(value.clone()),
+    };
+    let converts = transform.scale.is_some()
+        || transform.offset.is_some()
+        || (transform.source_unit.is_some() && mapping.unit.is_some());
+    match value {
+        SignalValue::Int(v) => {
+            let converted = apply_transform(*v as f64, transform, mapping.unit.as_deref())?;
+            if converts {
+                Ok(SignalValue::Float(converted))
+            } else {
+                Ok(SignalValue::Int(converted.round() as i64))
+            }
+        }
+        SignalValue::Float(v) => Ok(SignalValue::Float(apply_transform(
+            *v,
+            transform,
+            mapping.unit.as_deref(),
+        )?)),
+        _ => Ok(value.clone()),
+    }
+}
+
 pub fn get_mqtt_client_signal(
     mqtt_client_id: &str,
     identification_code: &str,
@@ -168,7 +223,9 @@ pub fn get_mqtt_client_signal(
             SignalMapping {
                 cache_size: signal.cache_size,
                 id: signal.id,
-                numb: signal.r#type.eq_ignore_ascii_case("数字"),
+                value_type: signal.value_type(),
+                unit: signal.unit.clone(),
+                transform: signal.transform.clone(),
             },
         );
     }
@@ -186,13 +243,60 @@ pub fn calc_bucket_name(prefix: &str, protocol: &str, id: u32) -> String {
 mod tests {
     use super::*;
     use common_lib::config::{get_config, read_config, read_config_tb};
+    use common_lib::influxdb_utils::{init_influx_writer, run_influx_writer};
     use common_lib::init_logger;
-    use common_lib::models::DataRow;
+    use common_lib::models::{DataRow, SignalTransform, SignalType};
     use common_lib::rabbit_utils::init_rabbitmq_with_config;
     use common_lib::redis_handler::init_redis;
     use common_lib::redis_pool_utils::create_redis_pool_from_config;
     use log::info;
 
+    #[test]
+    fn test_transform_value() {
+        let mut mapping = SignalMapping {
+            cache_size: 0,
+            id: 1,
+            value_type: SignalType::Float,
+            unit: Some("℃".to_string()),
+            transform: Some(SignalTransform {
+                scale: Some(0.1),
+                source_unit: Some("℉".to_string()),
+                ..Default::default()
+            }),
+        };
+        // 设备上报 0.1 ℉ 的计数
+        assert_eq!(
+            transform_value(&SignalValue::Float(2120.0), &mapping),
+            Ok(SignalValue::Float(100.0))
+        );
+        // 整数信号变换后为浮点数
+        assert_eq!(
+            transform_value(&SignalValue::Int(2120), &mapping),
+            Ok(SignalValue::Float(100.0))
+        );
+        assert_eq!(
+            transform_value(&SignalValue::from("on"), &mapping),
+            Ok(SignalValue::from("on"))
+        );
+
+        mapping.unit = Some("kg".to_string());
+        assert!(transform_value(&SignalValue::Float(1.0), &mapping).is_err());
+        // 只做范围限制时整数信号保持整数
+        mapping.transform = Some(SignalTransform {
+            max: Some(50.0),
+            ..Default::default()
+        });
+        assert_eq!(
+            transform_value(&SignalValue::Int(80), &mapping),
+            Ok(SignalValue::Int(50))
+        );
+        mapping.transform = None;
+        assert_eq!(
+            transform_value(&SignalValue::Float(1.0), &mapping),
+            Ok(SignalValue::Float(1.0))
+        );
+    }
+
     #[tokio::test]
     async fn test_storage() {
         init_logger();
@@ -203,18 +307,19 @@ mod tests {
         let redis_config = config.redis_config.clone();
         let influxdb = config.influx_config.clone().unwrap();
         init_redis(redis_config).await.unwrap();
+        init_influx_writer(influxdb.writer.clone()).unwrap();
         init_rabbitmq_with_config(config.mq_config.clone())
             .await
             .unwrap();
 
         let now = common_lib::time_utils::local_to_utc();
-        let dt = DataRowList {
+        let mut dt = DataRowList {
             Time: now,
             DeviceUid: "1".to_string(),
             IdentificationCode: "1".to_string(),
             DataRows: vec![DataRow {
                 Name: "信号-31".to_string(),
-                Value: "2".to_string(),
+                Value: "2".into(),
             }],
             Nc: "1".to_string(),
             Protocol: Some("MQTT".to_string()),
@@ -224,22 +329,68 @@ mod tests {
 
         let redisOp = RedisOp { pool };
         if let Err(e) = storage_data_row(
-            dt,
+            &mut dt,
             "MQTT",
-            influxdb.host.unwrap().as_str(),
-            influxdb.port.unwrap(),
-            influxdb.org.unwrap().as_str(),
-            influxdb.token.unwrap().as_str(),
-            influxdb.bucket.unwrap().as_str(),
+            influxdb.bucket.clone().unwrap().as_str(),
             &redisOp,
         )
         .await
         {
             log::error!("Failed to store data row: {:?}", e);
         }
+        // 运行一个写入周期, 将队列中的数据写入 InfluxDB
+        let _ = tokio::time::timeout(
+            std::time::Duration::from_secs(2),
+            run_influx_writer(&influxdb),
+        )
+        .await;
     }
 }
 
+fn signal_sample_key(device_uid: &str, identification_code: &str) -> String {
+    format!("signal_last_sample:{}:{}", device_uid, identification_code)
+}
+
+/// 读取信号最近两次样本
+pub fn get_signal_samples(
+    device_uid: &str,
+    identification_code: &str,
+    signal_id: i64,
+    redis: &RedisOp,
+) -> Result<Option<SignalSamples>, Box<dyn std::error::Error>> {
+    let key = signal_sample_key(device_uid, identification_code);
+    match redis.get_hash(key.as_str(), signal_id.to_string().as_str())? {
+        Some(value) => Ok(serde_json::from_str(&value).ok()),
+        None => Ok(None),
+    }
+}
+
+/// 记录新样本, 原最近样本成为上一样本
+///
+/// 同一数据行重复入库时只更新最近样本, 上报时间早于最近样本的乱序数据不记录
+fn set_signal_sample(
+    device_uid: &str,
+    identification_code: &str,
+    signal_id: i64,
+    sample: Tv,
+    redis: &RedisOp,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let prev = match get_signal_samples(device_uid, identification_code, signal_id, redis)? {
+        Some(samples) if samples.last.time == sample.time => samples.prev,
+        Some(samples) if samples.last.time > sample.time => return Ok(()),
+        Some(samples) => Some(samples.last),
+        None => None,
+    };
+    let samples = SignalSamples { last: sample, prev };
+    let key = signal_sample_key(device_uid, identification_code);
+    redis.set_hash(
+        key.as_str(),
+        signal_id.to_string().as_str(),
+        serde_json::to_string(&samples)?.as_str(),
+    )?;
+    Ok(())
+}
+
 pub fn set_push_time(
     protocol: &str,
     identification_code: &str,
@@ -260,7 +411,6 @@ pub fn set_push_time(
 
 async fn handler_data_storage_string(
     result: String,
-    jsc: Context,
     config: InfluxConfig,
     redis: &RedisOp,
     rabbit_conn: &Connection,
@@ -275,49 +425,30 @@ async fn handler_data_storage_string(
         .get_hash("mqtt_script", mqtt_message.mqtt_client_id.as_str())
         .unwrap();
 
+    let owner = format!("mqtt_script:{}", mqtt_message.mqtt_client_id);
     if let Some(string) = option {
-        // 在这里创建 JavaScript 上下文
-        jsc.eval(&string)
-            .map_err(|e| Box::new(e) as Box<dyn Error>)?;
-
-        // 调用 main 函数
-        let value = jsc
-            .call_function("main", [mqtt_message.message])
-            .map_err(|e| Box::new(e) as Box<dyn Error>)?;
-
-        // 定义并调用 JavaScript 代码
-        let js_code_2 = r#"
-        function main2(data) {
-            return JSON.stringify(data);
-        }"#;
-        jsc.eval(js_code_2)
-            .map_err(|e| Box::new(e) as Box<dyn Error>)?;
-        let value2 = jsc
-            .call_function("main2", [value])
-            .map_err(|e| Box::new(e) as Box<dyn Error>)?;
-
-        let x = value2.as_str().unwrap_or("");
+        // 在缓存的沙箱中调用解析脚本的 main 函数, 结果为 JSON 字符串
+        let x = call_cached(&owner, &string, "main", mqtt_message.message.as_str())?;
 
         info!("Java Script Result = {:?}", x);
-        let dt: Vec<DataRowList> = from_str(&x).map_err(|e| Box::new(e) as Box<dyn Error>)?;
+        let mut dt: Vec<DataRowList> = from_str(&x).map_err(|e| Box::new(e) as Box<dyn Error>)?;
         info!("{:?}", dt);
-
-        // 存储数据行
-        for mut data_row in dt {
+        for data_row in dt.iter_mut() {
             data_row.Protocol = Some("MQTT".to_string());
+        }
+        // 存储数据行, 数值信号按配置变换
+        for data_row in dt.iter_mut() {
             storage_data_row(
                 data_row,
                 "MQTT",
-                config.host.clone().unwrap().as_str(),
-                config.port.clone().unwrap(),
-                config.org.clone().unwrap().as_str(),
-                config.token.clone().unwrap().as_str(),
                 config.bucket.clone().unwrap().as_str(),
                 redis,
             )
             .await
             .expect("storage_data_row error");
         }
+        // 下游消息携带协议字段和变换后的值
+        let message = serde_json::to_string(&dt)?;
 
         // 创建 RabbitMQ 通道
         let rabbit_channel = rabbit_conn
@@ -332,7 +463,7 @@ async fn handler_data_storage_string(
                     "",
                     *queue,
                     BasicPublishOptions::default(),
-                    x.clone().as_bytes(),
+                    message.as_bytes(),
                     BasicProperties::default(),
                 )
                 .await
@@ -341,6 +472,8 @@ async fn handler_data_storage_string(
 
         // fixme: 处理最后推送时间（如果需要的话）
     } else {
+        // 脚本已删除, 释放缓存的沙箱
+        invalidate_cached(&owner);
         info!(
             "未找到脚本 for mqtt_client_id: {}",
             mqtt_message.mqtt_client_id
@@ -376,7 +509,6 @@ pub async fn pre_handler(
 
                 match handler_data_storage_string(
                     result,
-                    Context::new().unwrap(),
                     guard1.influx_config.clone().unwrap(),
                     guard,
                     rabbit_conn,
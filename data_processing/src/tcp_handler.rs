@@ -0,0 +1,139 @@
+use crate::storage_handler::storage_data_row;
+use common_lib::config::{Config, InfluxConfig};
+use common_lib::js_pool::{call_cached, invalidate_cached};
+use common_lib::models::{DataRowList, TcpMessage};
+use common_lib::redis_pool_utils::RedisOp;
+use futures_util::StreamExt;
+use lapin::options::{BasicAckOptions, BasicConsumeOptions, BasicPublishOptions};
+use lapin::types::FieldTable;
+use lapin::{BasicProperties, Channel, Connection};
+use log::{error, info};
+use serde_json::from_str;
+use std::error::Error;
+
+async fn handler_data_storage_string(
+    result: String,
+    config: InfluxConfig,
+    redis: &RedisOp,
+    rabbit_conn: &Connection,
+) -> Result<(), Box<dyn Error>> {
+    info!("message : {:?}", result);
+
+    let mqtt_message: TcpMessage = serde_json::from_str(&result)?;
+
+    // 获取存储的脚本
+    let option = redis.get_hash("struct:Tcp", mqtt_message.uid.as_str())?;
+
+    let owner = format!("struct:Tcp:{}", mqtt_message.uid);
+    if let Some(string) = option {
+        // 在缓存的沙箱中调用解析脚本的 main 函数, 结果为 JSON 字符串
+        let x = call_cached(&owner, &string, "main", mqtt_message.message.as_str())?;
+
+        info!("Java Script Result = {:?}", x);
+        let mut dt: Vec<DataRowList> = from_str(&x).map_err(|e| Box::new(e) as Box<dyn Error>)?;
+        info!("{:?}", dt);
+        for data_row in dt.iter_mut() {
+            data_row.Protocol = Some("TCP".to_string());
+        }
+        // 存储数据行, 数值信号按配置变换
+        for data_row in dt.iter_mut() {
+            storage_data_row(
+                data_row,
+                "TCP",
+                config.bucket.clone().unwrap().as_str(),
+                redis,
+            )
+            .await?;
+        }
+        // 下游消息携带协议字段和变换后的值
+        let message = serde_json::to_string(&dt)?;
+
+        // 创建 RabbitMQ 通道
+        let rabbit_channel = rabbit_conn
+            .create_channel()
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn Error>)?;
+
+        // 消息推送
+        for queue in ["waring_handler", "waring_delay_handler", "transmit_handler"].iter() {
+            rabbit_channel
+                .basic_publish(
+                    "",
+                    *queue,
+                    BasicPublishOptions::default(),
+                    message.as_bytes(),
+                    BasicProperties::default(),
+                )
+                .await
+                .map_err(|e| Box::new(e) as Box<dyn Error>)?;
+        }
+
+        // fixme: 处理最后推送时间（如果需要的话）
+    } else {
+        // 脚本已删除, 释放缓存的沙箱
+        invalidate_cached(&owner);
+        info!("未找到脚本 for uid: {}", mqtt_message.uid);
+    }
+
+    Ok(())
+}
+
+pub async fn pre_tcp_handler(
+    guard1: &Config,
+    guard: &RedisOp,
+    rabbit_conn: &Connection,
+    channel1: &Channel,
+) {
+    let mut consumer = channel1
+        .basic_consume(
+            "pre_tcp_handler",
+            "",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await
+        .unwrap();
+
+    info!("rmq consumer connected, waiting for messages");
+    while let Some(delivery_result) = consumer.next().await {
+        match delivery_result {
+            Ok(delivery) => {
+                info!("received msg: {:?}", delivery);
+
+                let result = String::from_utf8(delivery.data).unwrap();
+
+                match handler_data_storage_string(
+                    result,
+                    guard1.influx_config.clone().unwrap(),
+                    guard,
+                    rabbit_conn,
+                )
+                .await
+                {
+                    Ok(_) => {
+                        info!("msg processed");
+                    }
+                    Err(error) => {
+                        error!("{}", error);
+                    }
+                };
+
+                match channel1
+                    .basic_ack(delivery.delivery_tag, BasicAckOptions::default())
+                    .await
+                {
+                    Ok(_) => {
+                        info!("消息已成功确认。");
+                    }
+                    Err(e) => {
+                        error!("确认消息时发生错误: {}", e);
+                        // 这里可以添加进一步的错误处理逻辑
+                    }
+                }
+            }
+            Err(err) => {
+                error!("Error receiving message: {:?}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use crate::transmit::{Sink, SinkError};
+use async_trait::async_trait;
+use chrono::DateTime;
+use common_lib::models::DataRowList;
+use log::{debug, info};
+use scylla::batch::{Batch, BatchType};
+use scylla::frame::value::CqlTimestamp;
+use scylla::prepared_statement::PreparedStatement;
+use scylla::{Session, SessionBuilder};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CassandraSinkConfig {
+    pub nodes: Vec<String>, // 例如 ["127.0.0.1:9042"]
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub keyspace: String,
+    pub table: String,
+    #[serde(default = "default_replication_factor")]
+    pub replication_factor: u32, // 自动创建 keyspace 时使用
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize, // 单个 unlogged batch 的最大语句数
+}
+
+fn default_replication_factor() -> u32 {
+    1
+}
+
+fn default_batch_size() -> usize {
+    100
+}
+
+/// 一个信号值, 对应宽行中的一列
+type CassandraRow = (String, String, String, String, CqlTimestamp, String, String);
+
+pub struct CassandraSink {
+    config: CassandraSinkConfig,
+    session: Session,
+    insert: PreparedStatement,
+}
+
+impl CassandraSink {
+    pub async fn from_config(config: &str) -> Result<Self, SinkError> {
+        let config: CassandraSinkConfig = serde_json::from_str(config)?;
+
+        let mut builder = SessionBuilder::new().known_nodes(&config.nodes);
+        if let Some(username) = &config.username {
+            builder = builder.user(username, config.password.clone().unwrap_or_default());
+        }
+        let session = builder.build().await?;
+
+        for cql in create_schema_cql(&config) {
+            info!("cassandra: {}", cql);
+            session.query_unpaged(cql, &[]).await?;
+        }
+        let insert = session.prepare(insert_cql(&config)).await?;
+
+        Ok(CassandraSink {
+            config,
+            session,
+            insert,
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for CassandraSink {
+    fn name(&self) -> String {
+        format!("cassandra:{}.{}", self.config.keyspace, self.config.table)
+    }
+
+    async fn send(&self, rows: &[DataRowList]) -> Result<(), SinkError> {
+        // unlogged batch 只在同一分区内写入时才有意义, 按分区拆分
+        for (partition, values) in group_by_partition(rows) {
+            for chunk in values.chunks(self.config.batch_size.max(1)) {
+                debug!("cassandra insert {} rows into {:?}", chunk.len(), partition);
+                let mut batch = Batch::new(BatchType::Unlogged);
+                for _ in chunk {
+                    batch.append_statement(self.insert.clone());
+                }
+                self.session.batch(&batch, chunk.to_vec()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn create_schema_cql(config: &CassandraSinkConfig) -> Vec<String> {
+    let keyspace = quote_identifier(config.keyspace.as_str());
+    vec![
+        format!(
+            "CREATE KEYSPACE IF NOT EXISTS {} WITH replication = {{'class': 'SimpleStrategy', 'replication_factor': {}}}",
+            keyspace, config.replication_factor
+        ),
+        format!(
+            "CREATE TABLE IF NOT EXISTS {}.{} (\
+             protocol text, \
+             device_uid text, \
+             identification_code text, \
+             day text, \
+             time timestamp, \
+             name text, \
+             value text, \
+             PRIMARY KEY ((protocol, device_uid, identification_code, day), time, name)\
+             ) WITH CLUSTERING ORDER BY (time DESC, name ASC)",
+            keyspace,
+            quote_identifier(config.table.as_str())
+        ),
+    ]
+}
+
+fn insert_cql(config: &CassandraSinkConfig) -> String {
+    format!(
+        "INSERT INTO {}.{} (protocol, device_uid, identification_code, day, time, name, value) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        quote_identifier(config.keyspace.as_str()),
+        quote_identifier(config.table.as_str())
+    )
+}
+
+/// 按 (protocol, device_uid, identification_code, day) 分组
+fn group_by_partition(
+    rows: &[DataRowList],
+) -> HashMap<(String, String, String, String), Vec<CassandraRow>> {
+    let mut partitions: HashMap<(String, String, String, String), Vec<CassandraRow>> =
+        HashMap::new();
+    for dt in rows {
+        let protocol = dt.Protocol.clone().unwrap_or_default();
+        let day = partition_day(dt.Time);
+        let key = (
+            protocol.clone(),
+            dt.DeviceUid.clone(),
+            dt.IdentificationCode.clone(),
+            day.clone(),
+        );
+        let values = partitions.entry(key).or_default();
+        for x in &dt.DataRows {
+            values.push((
+                protocol.clone(),
+                dt.DeviceUid.clone(),
+                dt.IdentificationCode.clone(),
+                day.clone(),
+                CqlTimestamp(dt.Time * 1000),
+                x.Name.clone(),
+                x.Value.to_string(),
+            ));
+        }
+    }
+    partitions
+}
+
+fn partition_day(time: i64) -> String {
+    DateTime::from_timestamp(time, 0)
+        .map(|t| t.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_lib::models::DataRow;
+
+    fn data_row_list(time: i64) -> DataRowList {
+        DataRowList {
+            Time: time,
+            DeviceUid: "1".to_string(),
+            IdentificationCode: "2".to_string(),
+            DataRows: vec![
+                DataRow {
+                    Name: "Temperature".to_string(),
+                    Value: "23".into(),
+                },
+                DataRow {
+                    Name: "Humidity".to_string(),
+                    Value: "30".into(),
+                },
+            ],
+            Nc: "1".to_string(),
+            Protocol: Some("MQTT".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_group_by_partition() {
+        // 2024-10-27 23:59:59 与 2024-10-28 00:00:00 (UTC) 分属不同分区
+        let rows = vec![data_row_list(1730073599), data_row_list(1730073600)];
+        let partitions = group_by_partition(&rows);
+        assert_eq!(partitions.len(), 2);
+
+        let key = (
+            "MQTT".to_string(),
+            "1".to_string(),
+            "2".to_string(),
+            "2024-10-27".to_string(),
+        );
+        let values = &partitions[&key];
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].4, CqlTimestamp(1730073599000));
+        assert_eq!(values[1].5, "Humidity");
+    }
+
+    #[test]
+    fn test_schema_cql() {
+        let config: CassandraSinkConfig = serde_json::from_str(
+            r#"{"nodes":["127.0.0.1:9042"],"keyspace":"iot","table":"device_data"}"#,
+        )
+        .unwrap();
+        let cql = create_schema_cql(&config);
+        assert!(cql[0].contains("'replication_factor': 1"));
+        assert!(cql[1].starts_with("CREATE TABLE IF NOT EXISTS \"iot\".\"device_data\""));
+        assert!(cql[1].contains(
+            "PRIMARY KEY ((protocol, device_uid, identification_code, day), time, name)"
+        ));
+        assert!(insert_cql(&config).ends_with("VALUES (?, ?, ?, ?, ?, ?, ?)"));
+    }
+
+    /// 需要本地 Scylla: docker run -p 9042:9042 scylladb/scylla --smp 1
+    #[tokio::test]
+    #[ignore]
+    async fn test_cassandra_sink_send() {
+        let sink = CassandraSink::from_config(
+            r#"{"nodes":["127.0.0.1:9042"],"keyspace":"iot_test","table":"device_data"}"#,
+        )
+        .await
+        .unwrap();
+        sink.send(&[data_row_list(1730073599), data_row_list(1730073600)])
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+use crate::transmit::{Sink, SinkError};
+use async_trait::async_trait;
+use common_lib::models::DataRowList;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ClickhouseSinkConfig {
+    pub url: String, // HTTP 接口地址, 例如 http://127.0.0.1:8123
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub database: String,
+    pub table: String,
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize, // 单次 INSERT 的最大行数
+}
+
+fn default_batch_size() -> usize {
+    1000
+}
+
+/// JSONEachRow 的一行, 每个信号一行
+#[derive(Debug, Serialize)]
+struct ClickhouseRow<'a> {
+    protocol: &'a str,
+    device_uid: &'a str,
+    identification_code: &'a str,
+    time: i64,
+    name: &'a str,
+    value: String,
+    value_number: Option<f64>,
+    nc: &'a str,
+}
+
+pub struct ClickhouseSink {
+    config: ClickhouseSinkConfig,
+    client: reqwest::Client,
+    table_created: OnceCell<()>,
+}
+
+impl ClickhouseSink {
+    pub fn new(config: ClickhouseSinkConfig) -> Self {
+        ClickhouseSink {
+            config,
+            client: reqwest::Client::new(),
+            table_created: OnceCell::new(),
+        }
+    }
+
+    pub fn from_config(config: &str) -> Result<Self, SinkError> {
+        let config: ClickhouseSinkConfig = serde_json::from_str(config)?;
+        Ok(Self::new(config))
+    }
+
+    fn table_name(&self) -> String {
+        format!(
+            "{}.{}",
+            quote_identifier(self.config.database.as_str()),
+            quote_identifier(self.config.table.as_str())
+        )
+    }
+
+    fn create_table_sql(&self) -> String {
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (\
+             protocol LowCardinality(String), \
+             device_uid String, \
+             identification_code String, \
+             time DateTime, \
+             name String, \
+             value String, \
+             value_number Nullable(Float64), \
+             nc String\
+             ) ENGINE = MergeTree \
+             ORDER BY (protocol, device_uid, identification_code, time)",
+            self.table_name()
+        )
+    }
+
+    fn request(&self, query: Option<&str>, body: String) -> reqwest::RequestBuilder {
+        let mut builder = self.client.post(self.config.url.as_str()).body(body);
+        if let Some(query) = query {
+            builder = builder.query(&[("query", query)]);
+        }
+        if let Some(username) = &self.config.username {
+            builder = builder.basic_auth(username, self.config.password.as_ref());
+        }
+        builder
+    }
+
+    async fn execute(&self, query: Option<&str>, body: String) -> Result<(), SinkError> {
+        let response = self.request(query, body).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("clickhouse 返回 {}: {}", status, text).into());
+        }
+        Ok(())
+    }
+
+    async fn ensure_table(&self) -> Result<(), SinkError> {
+        self.table_created
+            .get_or_try_init(|| async {
+                info!("create clickhouse table {}", self.table_name());
+                self.execute(None, self.create_table_sql()).await
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for ClickhouseSink {
+    fn name(&self) -> String {
+        format!("clickhouse:{}", self.table_name())
+    }
+
+    async fn send(&self, rows: &[DataRowList]) -> Result<(), SinkError> {
+        self.ensure_table().await?;
+
+        let lines = to_json_each_row(rows)?;
+        let insert = format!("INSERT INTO {} FORMAT JSONEachRow", self.table_name());
+        for chunk in lines.chunks(self.config.batch_size.max(1)) {
+            debug!("clickhouse insert {} rows", chunk.len());
+            self.execute(Some(insert.as_str()), chunk.join("\n"))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+fn to_json_each_row(rows: &[DataRowList]) -> Result<Vec<String>, SinkError> {
+    let mut lines = Vec::new();
+    for dt in rows {
+        for x in &dt.DataRows {
+            let row = ClickhouseRow {
+                protocol: dt.Protocol.as_deref().unwrap_or(""),
+                device_uid: dt.DeviceUid.as_str(),
+                identification_code: dt.IdentificationCode.as_str(),
+                time: dt.Time,
+                name: x.Name.as_str(),
+                value: x.Value.to_string(),
+                value_number: x.Value.as_f64(),
+                nc: dt.Nc.as_str(),
+            };
+            lines.push(serde_json::to_string(&row)?);
+        }
+    }
+    Ok(lines)
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('\\', "\\\\").replace('`', "\\`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_lib::models::DataRow;
+    use common_lib::test_util::mock_server;
+
+    fn data_row_list(uid: &str, rows: Vec<(&str, &str)>) -> DataRowList {
+        DataRowList {
+            Time: 1730000000,
+            DeviceUid: uid.to_string(),
+            IdentificationCode: "1".to_string(),
+            DataRows: rows
+                .into_iter()
+                .map(|(name, value)| DataRow {
+                    Name: name.to_string(),
+                    Value: value.into(),
+                })
+                .collect(),
+            Nc: "1".to_string(),
+            Protocol: Some("MQTT".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clickhouse_sink_send() {
+        let (url, requests) = mock_server(vec![], "").await;
+        let config = format!(
+            r#"{{"url":"{}","database":"iot","table":"device_data","batch_size":2}}"#,
+            url
+        );
+        let sink = ClickhouseSink::from_config(config.as_str()).unwrap();
+
+        let rows = vec![
+            data_row_list("1", vec![("Temperature", "23.5"), ("Humidity", "30")]),
+            data_row_list("2", vec![("Status", "on")]),
+        ];
+        sink.send(&rows).await.unwrap();
+        sink.send(&rows[1..]).await.unwrap();
+
+        let requests = requests.lock().await;
+        // 建表一次, 第一批 3 行按 batch_size 拆成 2 次, 第二批 1 次
+        assert_eq!(requests.len(), 4);
+        assert!(requests[0]
+            .1
+            .starts_with("CREATE TABLE IF NOT EXISTS `iot`.`device_data`"));
+        assert!(requests[0]
+            .1
+            .contains("ORDER BY (protocol, device_uid, identification_code, time)"));
+
+        assert!(requests[1].0.contains("JSONEachRow"));
+        let lines: Vec<serde_json::Value> = requests[1]
+            .1
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["protocol"], "MQTT");
+        assert_eq!(lines[0]["device_uid"], "1");
+        assert_eq!(lines[0]["name"], "Temperature");
+        assert_eq!(lines[0]["value_number"], 23.5);
+        assert_eq!(requests[2].1.lines().count(), 1);
+        let status: serde_json::Value = serde_json::from_str(&requests[3].1).unwrap();
+        assert_eq!(status["value"], "on");
+        assert!(status["value_number"].is_null());
+    }
+
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("device_data"), "`device_data`");
+        assert_eq!(quote_identifier("a`b"), "`a\\`b`");
+    }
+}
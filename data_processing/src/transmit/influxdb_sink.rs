@@ -0,0 +1,216 @@
+use crate::storage_handler::get_mqtt_client_signal;
+use crate::transmit::{Sink, SinkError};
+use async_trait::async_trait;
+use common_lib::influxdb_utils::{InfluxDBManager, InfluxPoint};
+use common_lib::models::{DataRowList, DataValue, SignalMapping};
+use common_lib::redis_pool_utils::RedisOp;
+use log::{debug, error};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// 可用于 measurement 模板和 tag 的变量
+const TEMPLATE_VARS: [&str; 4] = ["protocol", "device_uid", "identification_code", "nc"];
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct InfluxSinkConfig {
+    pub host: String,
+    pub port: u16,
+    pub org: String,
+    pub token: String,
+    pub bucket: String,
+    #[serde(default = "default_measurement")]
+    pub measurement: String, // 模板, 例如 "{protocol}_{device_uid}_{identification_code}"
+    #[serde(default)]
+    pub tags: Vec<String>, // 写为 tag 的变量, 取值见 TEMPLATE_VARS
+}
+
+fn default_measurement() -> String {
+    "{protocol}_{device_uid}_{identification_code}".to_string()
+}
+
+pub struct InfluxSink {
+    config: InfluxSinkConfig,
+    db_manager: InfluxDBManager,
+    redis: RedisOp,
+}
+
+impl InfluxSink {
+    pub fn from_config(config: &str, redis: &RedisOp) -> Result<Self, SinkError> {
+        let config: InfluxSinkConfig = serde_json::from_str(config)?;
+        for tag in &config.tags {
+            if !TEMPLATE_VARS.contains(&tag.as_str()) {
+                return Err(format!("不支持的 tag: {}", tag).into());
+            }
+        }
+        let db_manager = InfluxDBManager::new(
+            config.host.as_str(),
+            config.port,
+            config.org.as_str(),
+            config.token.as_str(),
+        );
+        Ok(InfluxSink {
+            config,
+            db_manager,
+            redis: redis.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for InfluxSink {
+    fn name(&self) -> String {
+        format!(
+            "influxdb:{}:{}/{}",
+            self.config.host, self.config.port, self.config.bucket
+        )
+    }
+
+    async fn send(&self, rows: &[DataRowList]) -> Result<(), SinkError> {
+        let mut mappings: HashMap<String, HashMap<String, SignalMapping>> = HashMap::new();
+        let mut points = Vec::with_capacity(rows.len());
+        for dt in rows {
+            let key = format!("{}:{}", dt.DeviceUid, dt.IdentificationCode);
+            if !mappings.contains_key(&key) {
+                let mapping = get_mqtt_client_signal(
+                    dt.DeviceUid.as_str(),
+                    dt.IdentificationCode.as_str(),
+                    &self.redis,
+                )
+                .map_err(|e| e.to_string())?;
+                mappings.insert(key.clone(), mapping);
+            }
+            points.push(to_point(dt, &self.config, &mappings[&key]));
+        }
+
+        debug!("influxdb write {} points", points.len());
+        self.db_manager
+            .write_points(points, self.config.bucket.as_str())
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+fn template_var<'a>(dt: &'a DataRowList, name: &str) -> &'a str {
+    match name {
+        "protocol" => dt.Protocol.as_deref().unwrap_or(""),
+        "device_uid" => dt.DeviceUid.as_str(),
+        "identification_code" => dt.IdentificationCode.as_str(),
+        "nc" => dt.Nc.as_str(),
+        _ => "",
+    }
+}
+
+fn render_measurement(template: &str, dt: &DataRowList) -> String {
+    let mut measurement = template.to_string();
+    for name in TEMPLATE_VARS {
+        measurement = measurement.replace(&format!("{{{}}}", name), template_var(dt, name));
+    }
+    measurement
+}
+
+fn to_point(
+    dt: &DataRowList,
+    config: &InfluxSinkConfig,
+    mapping: &HashMap<String, SignalMapping>,
+) -> InfluxPoint {
+    let mut tags = HashMap::new();
+    for tag in &config.tags {
+        let value = template_var(dt, tag.as_str());
+        if !value.is_empty() {
+            tags.insert(tag.clone(), value.to_string());
+        }
+    }
+
+    let mut fields = HashMap::new();
+    for x in &dt.DataRows {
+        let value_type = mapping
+            .get(x.Name.as_str())
+            .map(|m| m.value_type)
+            .unwrap_or_default();
+        let value = match x.Value.coerce(value_type) {
+            Some(v) => v.to_data_value(),
+            None => {
+                error!("Failed to convert value {} to {:?}", x.Value, value_type);
+                continue;
+            }
+        };
+        fields.insert(x.Name.clone(), value);
+    }
+
+    InfluxPoint {
+        measurement: render_measurement(config.measurement.as_str(), dt),
+        tags,
+        fields,
+        timestamp: Some(dt.Time * 1_000_000_000),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_lib::models::{DataRow, SignalType};
+
+    #[test]
+    fn test_to_point() {
+        let config: InfluxSinkConfig = serde_json::from_str(
+            r#"{"host":"127.0.0.1","port":8086,"org":"myorg","token":"t","bucket":"b",
+                "measurement":"iot_{device_uid}","tags":["protocol","identification_code","nc"]}"#,
+        )
+        .unwrap();
+        let dt = DataRowList {
+            Time: 1730000000,
+            DeviceUid: "1".to_string(),
+            IdentificationCode: "2".to_string(),
+            DataRows: vec![
+                DataRow {
+                    Name: "Temperature".to_string(),
+                    Value: "23.5".into(),
+                },
+                DataRow {
+                    Name: "Status".to_string(),
+                    Value: "on".into(),
+                },
+            ],
+            Nc: "".to_string(),
+            Protocol: Some("MQTT".to_string()),
+        };
+        let mut mapping = HashMap::new();
+        mapping.insert(
+            "Temperature".to_string(),
+            SignalMapping {
+                cache_size: 0,
+                id: 1,
+                value_type: SignalType::Float,
+                unit: None,
+                transform: None,
+            },
+        );
+
+        let point = to_point(&dt, &config, &mapping);
+        assert_eq!(point.measurement, "iot_1");
+        assert_eq!(point.tags["protocol"], "MQTT");
+        assert_eq!(point.tags["identification_code"], "2");
+        // 空值不写为 tag
+        assert!(!point.tags.contains_key("nc"));
+        assert!(matches!(point.fields["Temperature"], DataValue::Float(v) if v == 23.5));
+        assert!(matches!(&point.fields["Status"], DataValue::Text(v) if v == "on"));
+        assert_eq!(point.timestamp, Some(1730000000 * 1_000_000_000));
+    }
+
+    #[test]
+    fn test_render_measurement_default() {
+        let dt = DataRowList {
+            Time: 0,
+            DeviceUid: "7".to_string(),
+            IdentificationCode: "8".to_string(),
+            DataRows: vec![],
+            Nc: "".to_string(),
+            Protocol: Some("TCP".to_string()),
+        };
+        assert_eq!(
+            render_measurement(default_measurement().as_str(), &dt),
+            "TCP_7_8"
+        );
+    }
+}
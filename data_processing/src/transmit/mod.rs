@@ -0,0 +1,165 @@
+pub mod cassandra_sink;
+pub mod clickhouse_sink;
+pub mod influxdb_sink;
+pub mod mongo_sink;
+pub mod mysql_sink;
+pub mod webhook_sink;
+
+use crate::transmit::cassandra_sink::CassandraSink;
+use crate::transmit::clickhouse_sink::ClickhouseSink;
+use crate::transmit::influxdb_sink::InfluxSink;
+use crate::transmit::mongo_sink::MongoSink;
+use crate::transmit::mysql_sink::MysqlSink;
+use crate::transmit::webhook_sink::WebhookSink;
+use async_trait::async_trait;
+use common_lib::models::{DataRowList, TransmitBind};
+use common_lib::redis_pool_utils::RedisOp;
+use log::{error, info};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// 构建失败后的重试间隔, 期间直接返回上次的错误
+const BUILD_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+pub type SinkError = Box<dyn Error + Send + Sync>;
+
+/// 转发目标
+#[async_trait]
+pub trait Sink: Send + Sync {
+    /// 转发目标名称, 用于日志
+    fn name(&self) -> String;
+
+    /// 写入一批数据, 返回 Ok 表示整批确认
+    async fn send(&self, rows: &[DataRowList]) -> Result<(), SinkError>;
+}
+
+/// 根据转发类型和配置 JSON 构建转发目标
+pub async fn build_sink(
+    sink_type: &str,
+    config: &str,
+    redis: &RedisOp,
+) -> Result<Arc<dyn Sink>, SinkError> {
+    match sink_type {
+        "clickhouse" => Ok(Arc::new(ClickhouseSink::from_config(config)?)),
+        "mysql" => Ok(Arc::new(MysqlSink::from_config(config).await?)),
+        "mongo" => Ok(Arc::new(MongoSink::from_config(config, redis).await?)),
+        "cassandra" => Ok(Arc::new(CassandraSink::from_config(config).await?)),
+        "influxdb" => Ok(Arc::new(InfluxSink::from_config(config, redis)?)),
+        "webhook" => Ok(Arc::new(WebhookSink::from_config(config)?)),
+        _ => Err(format!("unsupported sink type: {}", sink_type).into()),
+    }
+}
+
+enum SinkState {
+    Ready(Arc<dyn Sink>),
+    Failed { error: String, retry_at: Instant },
+}
+
+struct CachedSink {
+    config: String,
+    state: SinkState,
+}
+
+/// 转发分发器, 缓存已构建的转发目标, 确认/失败数记录在 transmit_stats:{类型}:{id} 哈希中
+pub struct TransmitDispatcher {
+    sinks: Mutex<HashMap<String, CachedSink>>,
+}
+
+impl TransmitDispatcher {
+    pub fn new() -> Self {
+        TransmitDispatcher {
+            sinks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 获取转发目标, 配置变更时重新构建
+    ///
+    /// 构建过程不持有缓存锁, 构建失败的配置在重试间隔内不再重复构建
+    pub async fn get_sink(
+        &self,
+        bind: &TransmitBind,
+        redis: &RedisOp,
+    ) -> Result<Arc<dyn Sink>, SinkError> {
+        let key = transmit_config_key(bind.sink_type.as_str());
+        let config = redis
+            .get_hash(key.as_str(), bind.sink_id.to_string().as_str())?
+            .ok_or_else(|| format!("未找到转发配置 {}:{}", key, bind.sink_id))?;
+
+        let cache_key = sink_cache_key(bind);
+        if let Some(cached) = self.sinks.lock().await.get(&cache_key) {
+            if cached.config == config {
+                match &cached.state {
+                    SinkState::Ready(sink) => return Ok(sink.clone()),
+                    SinkState::Failed { error, retry_at } if Instant::now() < *retry_at => {
+                        return Err(format!("构建转发目标失败, 等待重试: {}", error).into());
+                    }
+                    SinkState::Failed { .. } => {}
+                }
+            }
+        }
+
+        info!("build sink {}", cache_key);
+        let result = build_sink(bind.sink_type.as_str(), config.as_str(), redis).await;
+        let state = match &result {
+            Ok(sink) => SinkState::Ready(sink.clone()),
+            Err(e) => SinkState::Failed {
+                error: e.to_string(),
+                retry_at: Instant::now() + BUILD_RETRY_DELAY,
+            },
+        };
+        self.sinks
+            .lock()
+            .await
+            .insert(cache_key, CachedSink { config, state });
+        result
+    }
+
+    /// 分发一批数据到转发目标并记录结果
+    pub async fn dispatch(&self, bind: &TransmitBind, rows: &[DataRowList], redis: &RedisOp) {
+        let cache_key = sink_cache_key(bind);
+        let result = match self.get_sink(bind, redis).await {
+            Ok(sink) => sink.send(rows).await,
+            Err(e) => Err(e),
+        };
+
+        let count = rows.len() as u64;
+        let stats_key = format!("transmit_stats:{}", cache_key);
+        match result {
+            Ok(_) => {
+                info!("sink {} ack {} rows", cache_key, count);
+                if let Err(err) = redis.incr_hash(stats_key.as_str(), "ack", count as i64) {
+                    error!("记录转发统计失败: {}", err);
+                }
+            }
+            Err(e) => {
+                error!("sink {} failed {} rows: {}", cache_key, count, e);
+                if let Err(err) = redis.incr_hash(stats_key.as_str(), "fail", count as i64) {
+                    error!("记录转发统计失败: {}", err);
+                }
+                if let Err(err) =
+                    redis.set_hash(stats_key.as_str(), "last_error", e.to_string().as_str())
+                {
+                    error!("记录转发统计失败: {}", err);
+                }
+            }
+        }
+    }
+}
+
+pub fn transmit_config_key(sink_type: &str) -> String {
+    format!("transmit:{}", sink_type)
+}
+
+pub fn transmit_bind_key(device_uid: &str, identification_code: &str, protocol: &str) -> String {
+    format!(
+        "transmit_bind:{}:{}:{}",
+        protocol, device_uid, identification_code
+    )
+}
+
+fn sink_cache_key(bind: &TransmitBind) -> String {
+    format!("{}:{}", bind.sink_type, bind.sink_id)
+}
@@ -0,0 +1,223 @@
+use crate::storage_handler::get_mqtt_client_signal;
+use crate::transmit::{Sink, SinkError};
+use async_trait::async_trait;
+use bson::{doc, Bson, Document};
+use common_lib::config::MongoConfig;
+use common_lib::models::{DataRowList, SignalMapping, SignalValue};
+use common_lib::mongo_utils::MongoDBManager;
+use common_lib::redis_pool_utils::RedisOp;
+use log::{debug, error, info};
+use mongodb::options::IndexOptions;
+use mongodb::IndexModel;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MongoSinkConfig {
+    #[serde(flatten)]
+    pub mongo: MongoConfig, // collection 为转发规则使用的集合
+    pub ttl_seconds: Option<u64>, // 数据保留时间, 为空则不创建 TTL 索引
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize, // 单次 insert_many 的最大文档数
+}
+
+fn default_batch_size() -> usize {
+    500
+}
+
+pub struct MongoSink {
+    config: MongoSinkConfig,
+    collection: String,
+    mongo: MongoDBManager,
+    redis: RedisOp,
+    index_created: OnceCell<()>,
+}
+
+impl MongoSink {
+    pub async fn from_config(config: &str, redis: &RedisOp) -> Result<Self, SinkError> {
+        let config: MongoSinkConfig = serde_json::from_str(config)?;
+        let collection = config
+            .mongo
+            .collection
+            .clone()
+            .ok_or("mongo 转发配置缺少 collection")?;
+        let mongo = MongoDBManager::new(config.mongo.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(MongoSink {
+            config,
+            collection,
+            mongo,
+            redis: redis.clone(),
+            index_created: OnceCell::new(),
+        })
+    }
+
+    /// 创建 TTL 索引, 失败时只记录日志, 下一批数据写入时重试
+    async fn ensure_index(&self) {
+        let result = self
+            .index_created
+            .get_or_try_init(|| async {
+                if let Some(ttl) = self.config.ttl_seconds {
+                    let index = IndexModel::builder()
+                        .keys(doc! { "time": 1 })
+                        .options(
+                            IndexOptions::builder()
+                                .expire_after(Duration::from_secs(ttl))
+                                .build(),
+                        )
+                        .build();
+                    self.mongo
+                        .db
+                        .collection::<Document>(self.collection.as_str())
+                        .create_index(index)
+                        .await?;
+                    info!("mongo ttl index created on {}", self.collection);
+                }
+                Ok::<(), mongodb::error::Error>(())
+            })
+            .await;
+        if let Err(e) = result {
+            error!("创建 TTL 索引失败 {}: {}", self.collection, e);
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for MongoSink {
+    fn name(&self) -> String {
+        format!("mongo:{}", self.collection)
+    }
+
+    async fn send(&self, rows: &[DataRowList]) -> Result<(), SinkError> {
+        self.ensure_index().await;
+
+        let mut mappings: HashMap<String, HashMap<String, SignalMapping>> = HashMap::new();
+        let mut documents = Vec::with_capacity(rows.len());
+        for dt in rows {
+            let key = format!("{}:{}", dt.DeviceUid, dt.IdentificationCode);
+            if !mappings.contains_key(&key) {
+                let mapping = get_mqtt_client_signal(
+                    dt.DeviceUid.as_str(),
+                    dt.IdentificationCode.as_str(),
+                    &self.redis,
+                )
+                .map_err(|e| e.to_string())?;
+                mappings.insert(key.clone(), mapping);
+            }
+            documents.push(to_document(dt, &mappings[&key]));
+        }
+
+        let collection = self
+            .mongo
+            .db
+            .collection::<Document>(self.collection.as_str());
+        for chunk in documents.chunks(self.config.batch_size.max(1)) {
+            debug!("mongo insert {} documents", chunk.len());
+            collection.insert_many(chunk).await?;
+        }
+        Ok(())
+    }
+}
+
+/// 将一条 DataRowList 转换为文档, 信号值按配置的类型写入, 未配置的信号写为字符串
+fn to_document(dt: &DataRowList, mapping: &HashMap<String, SignalMapping>) -> Document {
+    let mut document = Document::new();
+    document.insert(
+        "time",
+        Bson::DateTime(bson::DateTime::from_millis(dt.Time * 1000)),
+    );
+    document.insert("device_uid", dt.DeviceUid.as_str());
+    document.insert("identification_code", dt.IdentificationCode.as_str());
+    document.insert("protocol", dt.Protocol.as_deref().unwrap_or(""));
+    document.insert("nc", dt.Nc.as_str());
+
+    let mut signals = Document::new();
+    for x in &dt.DataRows {
+        let value_type = mapping
+            .get(x.Name.as_str())
+            .map(|m| m.value_type)
+            .unwrap_or_default();
+        let value = match x.Value.coerce(value_type) {
+            Some(v) => to_bson(v),
+            None => {
+                error!("Failed to convert value {} to {:?}", x.Value, value_type);
+                Bson::String(x.Value.to_string())
+            }
+        };
+        signals.insert(x.Name.as_str(), value);
+    }
+    document.insert("signals", signals);
+    document
+}
+
+fn to_bson(value: SignalValue) -> Bson {
+    match value {
+        SignalValue::Bool(v) => Bson::Boolean(v),
+        SignalValue::Int(v) => Bson::Int64(v),
+        SignalValue::Float(v) => Bson::Double(v),
+        SignalValue::Text(v) => Bson::String(v),
+        SignalValue::Json(v) => Bson::try_from(v.clone()).unwrap_or(Bson::String(v.to_string())),
+        SignalValue::Geo(v) => Bson::Document(doc! { "lat": v.lat, "lon": v.lon }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_lib::models::{DataRow, SignalType};
+
+    #[test]
+    fn test_to_document() {
+        let dt = DataRowList {
+            Time: 1730000000,
+            DeviceUid: "1".to_string(),
+            IdentificationCode: "2".to_string(),
+            DataRows: vec![
+                DataRow {
+                    Name: "Temperature".to_string(),
+                    Value: "23.5".into(),
+                },
+                DataRow {
+                    Name: "Status".to_string(),
+                    Value: "on".into(),
+                },
+            ],
+            Nc: "1".to_string(),
+            Protocol: Some("MQTT".to_string()),
+        };
+        let mut mapping = HashMap::new();
+        mapping.insert(
+            "Temperature".to_string(),
+            SignalMapping {
+                cache_size: 0,
+                id: 1,
+                value_type: SignalType::Float,
+                unit: None,
+                transform: None,
+            },
+        );
+        mapping.insert(
+            "Status".to_string(),
+            SignalMapping {
+                cache_size: 0,
+                id: 2,
+                value_type: SignalType::Text,
+                unit: None,
+                transform: None,
+            },
+        );
+
+        let document = to_document(&dt, &mapping);
+        assert_eq!(
+            document.get_datetime("time").unwrap().timestamp_millis(),
+            1730000000000
+        );
+        assert_eq!(document.get_str("protocol").unwrap(), "MQTT");
+        let signals = document.get_document("signals").unwrap();
+        assert_eq!(signals.get_f64("Temperature").unwrap(), 23.5);
+        assert_eq!(signals.get_str("Status").unwrap(), "on");
+    }
+}
@@ -0,0 +1,250 @@
+use crate::transmit::{Sink, SinkError};
+use async_trait::async_trait;
+use common_lib::config::MySQLConfig;
+use common_lib::models::DataRowList;
+use common_lib::mysql_utils::MysqlOp;
+use log::{debug, info};
+use r2d2_mysql::mysql::Value;
+use serde::Deserialize;
+use tokio::sync::OnceCell;
+
+/// 数据字段与表字段的映射
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct MysqlColumnMapping {
+    pub time: String,
+    pub device_uid: String,
+    pub identification_code: String,
+    pub name: String,
+    pub value: String,
+    pub protocol: Option<String>, // 为空时不写入协议
+}
+
+impl Default for MysqlColumnMapping {
+    fn default() -> Self {
+        MysqlColumnMapping {
+            time: "time".to_string(),
+            device_uid: "device_uid".to_string(),
+            identification_code: "identification_code".to_string(),
+            name: "name".to_string(),
+            value: "value".to_string(),
+            protocol: Some("protocol".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MysqlSinkConfig {
+    #[serde(flatten)]
+    pub mysql: MySQLConfig,
+    pub table: String,
+    #[serde(default)]
+    pub columns: MysqlColumnMapping,
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize, // 单次批量执行的最大行数, 整批在同一个事务中提交
+}
+
+fn default_batch_size() -> usize {
+    500
+}
+
+pub struct MysqlSink {
+    config: MysqlSinkConfig,
+    mysql: MysqlOp,
+    table_created: OnceCell<()>,
+}
+
+impl MysqlSink {
+    /// 连接池在阻塞线程中创建, 避免占用异步运行时
+    pub async fn from_config(config: &str) -> Result<Self, SinkError> {
+        let config: MysqlSinkConfig = serde_json::from_str(config)?;
+        let mysql_config = config.mysql.clone();
+        let mysql = tokio::task::spawn_blocking(move || MysqlOp::try_new(mysql_config)).await??;
+        Ok(MysqlSink {
+            config,
+            mysql,
+            table_created: OnceCell::new(),
+        })
+    }
+
+    async fn ensure_table(&self) -> Result<(), SinkError> {
+        self.table_created
+            .get_or_try_init(|| async {
+                let sql = create_table_sql(self.config.table.as_str(), &self.config.columns);
+                info!("create mysql table: {}", sql);
+                let mysql = self.mysql.clone();
+                tokio::task::spawn_blocking(move || mysql.execute(sql.as_str())).await?
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for MysqlSink {
+    fn name(&self) -> String {
+        format!("mysql:{}.{}", self.config.mysql.dbname, self.config.table)
+    }
+
+    async fn send(&self, rows: &[DataRowList]) -> Result<(), SinkError> {
+        self.ensure_table().await?;
+
+        let sql = insert_sql(self.config.table.as_str(), &self.config.columns);
+        let params = to_params(rows, &self.config.columns);
+        debug!("mysql insert {} rows", params.len());
+        // 整批在一个事务中写入, 失败时整体回滚, 与整批失败的统计一致
+        let mysql = self.mysql.clone();
+        let batch_size = self.config.batch_size;
+        tokio::task::spawn_blocking(move || {
+            mysql.exec_batch_in_transaction(sql.as_str(), params, batch_size)
+        })
+        .await??;
+        Ok(())
+    }
+}
+
+fn column_names(columns: &MysqlColumnMapping) -> Vec<&str> {
+    let mut names = vec![
+        columns.time.as_str(),
+        columns.device_uid.as_str(),
+        columns.identification_code.as_str(),
+        columns.name.as_str(),
+        columns.value.as_str(),
+    ];
+    if let Some(protocol) = &columns.protocol {
+        names.push(protocol.as_str());
+    }
+    names
+}
+
+fn create_table_sql(table: &str, columns: &MysqlColumnMapping) -> String {
+    let mut defs = vec![
+        "`id` BIGINT NOT NULL AUTO_INCREMENT".to_string(),
+        format!(
+            "{} BIGINT NOT NULL",
+            quote_identifier(columns.time.as_str())
+        ),
+        format!(
+            "{} VARCHAR(64) NOT NULL",
+            quote_identifier(columns.device_uid.as_str())
+        ),
+        format!(
+            "{} VARCHAR(64) NOT NULL",
+            quote_identifier(columns.identification_code.as_str())
+        ),
+        format!(
+            "{} VARCHAR(128) NOT NULL",
+            quote_identifier(columns.name.as_str())
+        ),
+        format!("{} TEXT", quote_identifier(columns.value.as_str())),
+    ];
+    if let Some(protocol) = &columns.protocol {
+        defs.push(format!(
+            "{} VARCHAR(16)",
+            quote_identifier(protocol.as_str())
+        ));
+    }
+    defs.push("PRIMARY KEY (`id`)".to_string());
+    defs.push(format!(
+        "INDEX `idx_device_time` ({}, {}, {})",
+        quote_identifier(columns.device_uid.as_str()),
+        quote_identifier(columns.identification_code.as_str()),
+        quote_identifier(columns.time.as_str())
+    ));
+
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} ({})",
+        quote_identifier(table),
+        defs.join(", ")
+    )
+}
+
+fn insert_sql(table: &str, columns: &MysqlColumnMapping) -> String {
+    let names = column_names(columns);
+    let quoted: Vec<String> = names.iter().map(|n| quote_identifier(n)).collect();
+    let placeholders = vec!["?"; names.len()].join(", ");
+    format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        quote_identifier(table),
+        quoted.join(", "),
+        placeholders
+    )
+}
+
+fn to_params(rows: &[DataRowList], columns: &MysqlColumnMapping) -> Vec<Vec<Value>> {
+    let mut params = Vec::new();
+    for dt in rows {
+        for x in &dt.DataRows {
+            let mut row = vec![
+                Value::from(dt.Time),
+                Value::from(dt.DeviceUid.as_str()),
+                Value::from(dt.IdentificationCode.as_str()),
+                Value::from(x.Name.as_str()),
+                Value::from(x.Value.to_string()),
+            ];
+            if columns.protocol.is_some() {
+                row.push(Value::from(dt.Protocol.as_deref().unwrap_or("")));
+            }
+            params.push(row);
+        }
+    }
+    params
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_lib::models::DataRow;
+
+    #[test]
+    fn test_insert_sql_with_mapping() {
+        let columns: MysqlColumnMapping =
+            serde_json::from_str(r#"{"time":"ts","value":"val","protocol":null}"#).unwrap();
+        assert_eq!(
+            insert_sql("device_data", &columns),
+            "INSERT INTO `device_data` (`ts`, `device_uid`, `identification_code`, `name`, `val`) VALUES (?, ?, ?, ?, ?)"
+        );
+    }
+
+    #[test]
+    fn test_create_table_sql() {
+        let sql = create_table_sql("device_data", &MysqlColumnMapping::default());
+        assert!(sql.starts_with("CREATE TABLE IF NOT EXISTS `device_data` ("));
+        assert!(sql.contains("`protocol` VARCHAR(16)"));
+        assert!(
+            sql.contains("INDEX `idx_device_time` (`device_uid`, `identification_code`, `time`)")
+        );
+        assert_eq!(quote_identifier("a`b"), "`a``b`");
+    }
+
+    #[test]
+    fn test_to_params() {
+        let rows = vec![DataRowList {
+            Time: 1730000000,
+            DeviceUid: "1".to_string(),
+            IdentificationCode: "2".to_string(),
+            DataRows: vec![
+                DataRow {
+                    Name: "Temperature".to_string(),
+                    Value: "23".into(),
+                },
+                DataRow {
+                    Name: "Humidity".to_string(),
+                    Value: "30".into(),
+                },
+            ],
+            Nc: "1".to_string(),
+            Protocol: Some("MQTT".to_string()),
+        }];
+
+        let params = to_params(&rows, &MysqlColumnMapping::default());
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[1][0], Value::from(1730000000i64));
+        assert_eq!(params[1][3], Value::from("Humidity"));
+        assert_eq!(params[1][5], Value::from("MQTT"));
+    }
+}
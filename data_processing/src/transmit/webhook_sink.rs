@@ -0,0 +1,360 @@
+use crate::transmit::{Sink, SinkError};
+use async_trait::async_trait;
+use common_lib::js_pool::{call_cached_json, script_hash};
+use common_lib::models::DataRowList;
+use futures_util::future::join_all;
+use hmac::{Hmac, Mac};
+use log::{debug, warn};
+use reqwest::Method;
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// 按地址共享的并发限制, 多个转发目标指向同一地址时共用, 并发数以首次创建时的配置为准
+static ENDPOINT_SEMAPHORES: OnceLock<Mutex<HashMap<String, Arc<Semaphore>>>> = OnceLock::new();
+
+fn endpoint_semaphore(url: &str, permits: usize) -> Arc<Semaphore> {
+    let semaphores = ENDPOINT_SEMAPHORES.get_or_init(|| Mutex::new(HashMap::new()));
+    semaphores
+        .lock()
+        .unwrap()
+        .entry(url.to_string())
+        .or_insert_with(|| Arc::new(Semaphore::new(permits.max(1))))
+        .clone()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebhookSinkConfig {
+    pub url: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub secret: Option<String>, // HMAC-SHA256 签名密钥, 为空则不签名
+    #[serde(default = "default_signature_header")]
+    pub signature_header: String,
+    pub script: Option<String>,   // JS 转换脚本, 需要定义 main(data)
+    pub template: Option<String>, // 文本模板, script 为空时使用
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64, // 首次重试等待时间, 之后每次翻倍
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize, // 同一地址的最大并发请求数
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+fn default_signature_header() -> String {
+    "X-Signature".to_string()
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
+}
+
+fn default_max_concurrency() -> usize {
+    4
+}
+
+fn default_timeout_ms() -> u64 {
+    10_000
+}
+
+enum PostError {
+    Retryable(SinkError),
+    Fatal(SinkError),
+}
+
+pub struct WebhookSink {
+    config: WebhookSinkConfig,
+    method: Method,
+    client: reqwest::Client,
+    semaphore: Arc<Semaphore>,
+    content_type_header: bool, // headers 中未配置 Content-Type 时按请求体设置
+    script_owner: String,      // 转换脚本的沙箱 owner, 按配置区分同一地址的不同转发规则
+}
+
+impl WebhookSink {
+    pub fn from_config(config: &str) -> Result<Self, SinkError> {
+        let script_owner = format!("webhook:{:x}", script_hash(config));
+        let config: WebhookSinkConfig = serde_json::from_str(config)?;
+        let method = Method::from_bytes(config.method.to_ascii_uppercase().as_bytes())?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()?;
+        let semaphore = endpoint_semaphore(config.url.as_str(), config.max_concurrency);
+        let content_type_header = !config
+            .headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case("content-type"));
+        Ok(WebhookSink {
+            config,
+            method,
+            client,
+            semaphore,
+            content_type_header,
+            script_owner,
+        })
+    }
+
+    async fn post(&self, body: &str) -> Result<(), PostError> {
+        let mut builder = self
+            .client
+            .request(self.method.clone(), self.config.url.as_str());
+        if self.content_type_header {
+            builder = builder.header("Content-Type", content_type(body));
+        }
+        for (name, value) in &self.config.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        if let Some(secret) = &self.config.secret {
+            builder = builder.header(
+                self.config.signature_header.as_str(),
+                format!("sha256={}", sign(secret.as_str(), body)),
+            );
+        }
+
+        let response = builder
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|e| PostError::Retryable(Box::new(e)))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let err: SinkError = format!("webhook {} 返回 {}", self.config.url, status).into();
+        if status.is_server_error() || status.as_u16() == 429 {
+            Err(PostError::Retryable(err))
+        } else {
+            Err(PostError::Fatal(err))
+        }
+    }
+
+    /// 每次请求单独占用并发名额, 重试等待期间不占用
+    async fn post_with_retry(&self, body: String) -> Result<(), SinkError> {
+        let mut backoff = Duration::from_millis(self.config.backoff_ms);
+        let max_backoff = Duration::from_millis(self.config.max_backoff_ms);
+        let mut attempt = 0;
+        loop {
+            let result = {
+                let _permit = self.semaphore.acquire().await?;
+                self.post(body.as_str()).await
+            };
+            match result {
+                Ok(_) => return Ok(()),
+                Err(PostError::Retryable(e)) if attempt < self.config.max_retries => {
+                    attempt += 1;
+                    warn!(
+                        "webhook {} 第 {} 次重试, {:?} 后执行: {}",
+                        self.config.url, attempt, backoff, e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(max_backoff);
+                }
+                Err(PostError::Retryable(e)) | Err(PostError::Fatal(e)) => return Err(e),
+            }
+        }
+    }
+
+    fn render_body(&self, dt: &DataRowList) -> Result<String, SinkError> {
+        if let Some(script) = &self.config.script {
+            render_script(self.script_owner.as_str(), script.as_str(), dt)
+        } else if let Some(template) = &self.config.template {
+            render_template(template.as_str(), dt)
+        } else {
+            Ok(serde_json::to_string(dt)?)
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for WebhookSink {
+    fn name(&self) -> String {
+        format!("webhook:{} {}", self.method, self.config.url)
+    }
+
+    async fn send(&self, rows: &[DataRowList]) -> Result<(), SinkError> {
+        let mut bodies = Vec::with_capacity(rows.len());
+        for dt in rows {
+            bodies.push(self.render_body(dt)?);
+        }
+
+        let results = join_all(bodies.into_iter().map(|body| self.post_with_retry(body))).await;
+        let errors: Vec<String> = results
+            .into_iter()
+            .filter_map(|r| r.err().map(|e| e.to_string()))
+            .collect();
+        if errors.is_empty() {
+            debug!("webhook {} sent {} requests", self.config.url, rows.len());
+            Ok(())
+        } else {
+            Err(format!(
+                "{}/{} webhook requests failed: {}",
+                errors.len(),
+                rows.len(),
+                errors.join("; ")
+            )
+            .into())
+        }
+    }
+}
+
+/// 请求体为 JSON 时使用 application/json, 否则按纯文本发送
+fn content_type(body: &str) -> &'static str {
+    if serde_json::from_str::<serde::de::IgnoredAny>(body).is_ok() {
+        "application/json"
+    } else {
+        "text/plain; charset=utf-8"
+    }
+}
+
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// 执行转换脚本, main 返回字符串时原样发送, 否则序列化为 JSON
+fn render_script(owner: &str, script: &str, dt: &DataRowList) -> Result<String, SinkError> {
+    let value = call_cached_json(owner, script, "main", serde_json::to_string(dt)?.as_str())?;
+    match serde_json::from_str(value.as_str())? {
+        serde_json::Value::String(body) => Ok(body),
+        _ => Ok(value),
+    }
+}
+
+/// 文本模板, 支持 {{device_uid}} {{identification_code}} {{protocol}} {{nc}} {{time}}
+/// {{rows}} {{data}} 以及 {{signal.<信号名>}}
+fn render_template(template: &str, dt: &DataRowList) -> Result<String, SinkError> {
+    let mut body = template
+        .replace("{{device_uid}}", dt.DeviceUid.as_str())
+        .replace("{{identification_code}}", dt.IdentificationCode.as_str())
+        .replace("{{protocol}}", dt.Protocol.as_deref().unwrap_or(""))
+        .replace("{{nc}}", dt.Nc.as_str())
+        .replace("{{time}}", dt.Time.to_string().as_str());
+    if body.contains("{{rows}}") {
+        body = body.replace("{{rows}}", serde_json::to_string(&dt.DataRows)?.as_str());
+    }
+    if body.contains("{{data}}") {
+        body = body.replace("{{data}}", serde_json::to_string(dt)?.as_str());
+    }
+    for x in &dt.DataRows {
+        body = body.replace(
+            &format!("{{{{signal.{}}}}}", x.Name),
+            x.Value.to_string().as_str(),
+        );
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_lib::models::DataRow;
+    use common_lib::test_util::mock_server;
+
+    fn data_row_list() -> DataRowList {
+        DataRowList {
+            Time: 1730000000,
+            DeviceUid: "1".to_string(),
+            IdentificationCode: "2".to_string(),
+            DataRows: vec![DataRow {
+                Name: "Temperature".to_string(),
+                Value: "23".into(),
+            }],
+            Nc: "1".to_string(),
+            Protocol: Some("MQTT".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        let body = render_template(
+            r#"{"uid":"{{device_uid}}","t":{{signal.Temperature}},"at":{{time}}}"#,
+            &data_row_list(),
+        )
+        .unwrap();
+        assert_eq!(body, r#"{"uid":"1","t":23,"at":1730000000}"#);
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(content_type(r#"{"uid":"1"}"#), "application/json");
+        assert_eq!(content_type("[1,2]"), "application/json");
+        assert_eq!(content_type("1:23"), "text/plain; charset=utf-8");
+        assert_eq!(content_type("<xml/>"), "text/plain; charset=utf-8");
+    }
+
+    #[test]
+    fn test_endpoint_semaphore_shared() {
+        let a = endpoint_semaphore("http://shared.example/hook", 2);
+        let b = endpoint_semaphore("http://shared.example/hook", 8);
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(b.available_permits(), 2);
+    }
+
+    #[test]
+    fn test_render_script() {
+        let script = r#"
+            function main(data) {
+                return { device: data.DeviceUid, value: Number(data.DataRows[0].Value) };
+            }
+        "#;
+        let body = render_script("webhook", script, &data_row_list()).unwrap();
+        assert_eq!(body, r#"{"device":"1","value":23}"#);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_retry_and_sign() {
+        let (url, requests) = mock_server(vec![500, 503, 200], "").await;
+        let config = serde_json::json!({
+            "url": url,
+            "headers": {"X-Token": "abc"},
+            "secret": "key",
+            "template": "{{device_uid}}:{{signal.Temperature}}",
+            "backoff_ms": 10,
+        });
+        let sink = WebhookSink::from_config(config.to_string().as_str()).unwrap();
+        sink.send(&[data_row_list()]).await.unwrap();
+
+        let requests = requests.lock().await;
+        assert_eq!(requests.len(), 3);
+        let (head, body) = &requests[2];
+        assert_eq!(body, "1:23");
+        let head = head.to_ascii_lowercase();
+        assert!(head.starts_with("post "));
+        assert!(head.contains("x-token: abc"));
+        assert!(head.contains("content-type: text/plain; charset=utf-8"));
+        assert!(head.contains(format!("x-signature: sha256={}", sign("key", "1:23")).as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_client_error_not_retried() {
+        let (url, requests) = mock_server(vec![400], "").await;
+        let config = serde_json::json!({ "url": url, "backoff_ms": 10 });
+        let sink = WebhookSink::from_config(config.to_string().as_str()).unwrap();
+        assert!(sink.send(&[data_row_list()]).await.is_err());
+        assert_eq!(requests.lock().await.len(), 1);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::transmit::{transmit_bind_key, TransmitDispatcher};
+use common_lib::models::{DataRowList, TransmitBind};
+use common_lib::redis_pool_utils::RedisOp;
+use futures_util::StreamExt;
+use lapin::options::{BasicAckOptions, BasicConsumeOptions};
+use lapin::types::FieldTable;
+use lapin::Channel;
+use log::{debug, error, info};
+use std::collections::HashMap;
+use std::error::Error;
+
+pub async fn handler_transmit_string(
+    result: String,
+    redis: &RedisOp,
+    dispatcher: &TransmitDispatcher,
+) -> Result<(), Box<dyn Error>> {
+    info!("message : {:?}", result);
+
+    let dt: Vec<DataRowList> = serde_json::from_str(&result)?;
+
+    // 按转发目标聚合, 同一目标的数据一次写入
+    let mut batches: HashMap<String, (TransmitBind, Vec<DataRowList>)> = HashMap::new();
+    for row in dt {
+        let protocol = row.Protocol.clone().unwrap_or_default();
+        let binds = get_transmit_bind(
+            row.DeviceUid.as_str(),
+            row.IdentificationCode.as_str(),
+            protocol.as_str(),
+            redis,
+        )?;
+
+        for bind in binds {
+            if !bind.enable {
+                continue;
+            }
+            let key = format!("{}:{}", bind.sink_type, bind.sink_id);
+            batches
+                .entry(key)
+                .or_insert_with(|| (bind, Vec::new()))
+                .1
+                .push(row.clone());
+        }
+    }
+
+    for (_, (bind, rows)) in batches {
+        dispatcher.dispatch(&bind, &rows, redis).await;
+    }
+
+    Ok(())
+}
+
+pub fn get_transmit_bind(
+    device_uid: &str,
+    identification_code: &str,
+    protocol: &str,
+    redis: &RedisOp,
+) -> Result<Vec<TransmitBind>, Box<dyn Error>> {
+    let key = transmit_bind_key(device_uid, identification_code, protocol);
+    debug!("key = {}", key);
+
+    let mut binds = Vec::new();
+    for value in redis.get_list_all(key.as_str())? {
+        match serde_json::from_str::<TransmitBind>(&value) {
+            Ok(bind) => binds.push(bind),
+            Err(e) => {
+                error!("转发规则反序列化失败: {}", e);
+            }
+        }
+    }
+    Ok(binds)
+}
+
+pub async fn transmit_handler(guard: &RedisOp, channel1: &Channel) {
+    let dispatcher = TransmitDispatcher::new();
+
+    let mut consumer = channel1
+        .basic_consume(
+            "transmit_handler",
+            "",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await
+        .unwrap();
+
+    info!("rmq consumer connected, waiting for messages");
+    while let Some(delivery_result) = consumer.next().await {
+        match delivery_result {
+            Ok(delivery) => {
+                info!("received msg: {:?}", delivery);
+
+                let result = String::from_utf8(delivery.data).unwrap();
+
+                match handler_transmit_string(result, guard, &dispatcher).await {
+                    Ok(_) => {
+                        info!("msg processed");
+                    }
+                    Err(error) => {
+                        error!("{}", error);
+                    }
+                };
+
+                match channel1
+                    .basic_ack(delivery.delivery_tag, BasicAckOptions::default())
+                    .await
+                {
+                    Ok(_) => {
+                        info!("消息已成功确认。");
+                    }
+                    Err(e) => {
+                        error!("确认消息时发生错误: {}", e);
+                    }
+                }
+            }
+            Err(err) => {
+                error!("Error receiving message: {:?}", err);
+            }
+        }
+    }
+}
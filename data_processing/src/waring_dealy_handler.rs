@@ -1,8 +1,15 @@
+use crate::alarm_state::{
+    alarm_state_key, commit_alarm_state, prepare_alarm_state, record_alarm_transition,
+};
+use crate::escalation::schedule_escalation;
+use crate::silence::is_silenced;
 use chrono::Utc;
-use common_lib::models::{DataRow, DataRowList, SignalDelayWaring, SignalDelayWaringParam, Tv};
+use common_lib::models::{
+    AlarmEvent, AlarmRuleType, DataRow, DataRowList, SignalDelayWaring, SignalDelayWaringParam, Tv,
+};
 use common_lib::redis_handler::{get_redis_instance, RedisWrapper};
 use futures_util::StreamExt;
-use lapin::options::{BasicAckOptions, BasicConsumeOptions};
+use lapin::options::{BasicAckOptions, BasicConsumeOptions, BasicNackOptions};
 use lapin::types::FieldTable;
 use lapin::{Channel, Connection};
 use log::info;
@@ -16,6 +23,7 @@ pub async fn handler_waring_delay_once(
     script_waring_collection: String,
     redis: &RedisOp,
     mongo_dbmanager: &MongoDBManager,
+    channel: &Channel,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let device_uid_string = &*dt.DeviceUid;
     let iden_code = &*dt.IdentificationCode;
@@ -49,9 +57,48 @@ pub async fn handler_waring_delay_once(
     let script = get_delay_script(mapping, redis).unwrap();
     let now = common_lib::time_utils::local_to_utc();
     for x in script {
-        let js = call_js(x.script.clone(), &script_param);
+        let js = match call_js(x.id, x.script.clone(), &script_param) {
+            Ok(js) => js,
+            Err(e) => {
+                error!("报警脚本执行失败 rule_id = {}: {}", x.id, e);
+                continue;
+            }
+        };
         info!("js = {}", js);
 
+        // 脚本返回 true 视为命中报警, 只有 FIRING/RESOLVED 状态迁移才产生报警记录
+        let state_key =
+            alarm_state_key(AlarmRuleType::Script, x.id, device_uid_string, iden_code, 0);
+        let update = match prepare_alarm_state(state_key.as_str(), js, push_time, redis)? {
+            Some(update) => update,
+            None => continue,
+        };
+        let state = &update.state;
+        info!("报警状态迁移 {:?} rule_id = {}", state.status, x.id);
+
+        let mut event = AlarmEvent {
+            rule_id: x.id,
+            rule_type: AlarmRuleType::Script,
+            device_uid: device_uid_string.to_string(),
+            identification_code: iden_code.to_string(),
+            protocol: dt.Protocol.clone(),
+            signal_id: None,
+            signal_name: None,
+            value: None,
+            param: Some(script_param.clone()),
+            time: push_time,
+            insert_time: now,
+            severity: x.severity,
+            status: state.status,
+            duration: state.resolved_at.map(|_| state.duration()),
+            alarm_id: None,
+            silenced: false,
+            escalation_level: 0,
+        };
+        // 命中静默规则的报警照常记录和发布, 由通知服务跳过
+        event.silenced = is_silenced(&event, now, redis)?;
+        record_alarm_transition(state_key.as_str(), state, &mut event, mongo_dbmanager).await?;
+
         let mut document = HashMap::new();
         document.insert(
             "device_uid".to_string(),
@@ -63,18 +110,23 @@ pub async fn handler_waring_delay_once(
         document.insert("rule_id".to_string(), serde_json::json!(x.id));
         document.insert("insert_time".to_string(), serde_json::json!(now));
         document.insert("up_time".to_string(), serde_json::json!(dt.Time));
+        document.insert("severity".to_string(), serde_json::json!(event.severity));
+        document.insert("status".to_string(), serde_json::json!(event.status));
+        document.insert("fired_at".to_string(), serde_json::json!(state.fired_at));
+        document.insert("duration".to_string(), serde_json::json!(event.duration));
+        document.insert("silenced".to_string(), serde_json::json!(event.silenced));
 
         let name = calc_collection_name(script_waring_collection.as_str(), x.id);
 
-        mongo_dbmanager
-            .create_collection(name.as_str())
-            .await
-            .unwrap();
-
+        mongo_dbmanager.create_collection(name.as_str()).await?;
         mongo_dbmanager
             .insert_document(name.as_str(), document)
-            .await
-            .unwrap();
+            .await?;
+
+        publish_alarm_event(channel, &event).await?;
+        schedule_escalation(&event, redis)?;
+        // 记录和发布成功后再提交状态, 失败时消息重新投递仍会重新处理该迁移
+        commit_alarm_state(state_key.as_str(), &update, redis)?;
     }
 
     Ok(())
@@ -144,38 +196,23 @@ fn get_delay_script(
 
     Ok(unique_res)
 }
-use crate::waring_handler::calc_collection_name;
+use crate::waring_handler::{calc_collection_name, publish_alarm_event};
 use common_lib::config::InfluxConfig;
+use common_lib::js_pool::call_cached_json;
 use common_lib::mongo_utils::MongoDBManager;
 use common_lib::redis_pool_utils::RedisOp;
-use quick_js::{Context, JsValue};
 use tokio::sync::MutexGuard;
 
-fn call_js(js: String, param: &HashMap<String, Vec<Tv>>) -> bool {
-    // 创建新的 JavaScript 上下文
-    let context = Context::new().unwrap();
+fn call_js(id: i32, js: String, param: &HashMap<String, Vec<Tv>>) -> Result<bool, Box<dyn Error>> {
+    // 将 HashMap 转换为 JSON 字符串
+    let json_param = serde_json::to_string(&param)?;
 
-    // 执行 JavaScript 代码
-    context.eval(js.as_str()).unwrap();
+    // 在缓存的沙箱中调用脚本的 main 函数, 只有返回 true 视为命中
+    let owner = format!("signal_delay_config:{}", id);
+    let value = call_cached_json(&owner, js.as_str(), "main", json_param.as_str())?;
+    info!("value = {:?}", value);
 
-    // 将 HashMap 转换为 JSON 字符串
-    let json_param = serde_json::to_string(&param).unwrap();
-
-    let js_code_2 = r#"
-        function main2(data) {
-            return JSON.parse(data);
-        }"#;
-    context.eval(js_code_2).unwrap();
-    let value2 = context.call_function("main2", [json_param]).unwrap();
-    info!("value2 = {:?}", value2);
-
-    // 调用 JavaScript 函数，传递 JSON 参数
-    let value = context.call_function("main", [value2]).unwrap();
-
-    match value {
-        JsValue::Bool(b) => b,
-        _ => false,
-    }
+    Ok(value == "true")
 }
 
 #[cfg(test)]
@@ -185,7 +222,7 @@ mod tests {
     use common_lib::config::{get_config, read_config, read_config_tb};
     use common_lib::init_logger;
     use common_lib::mongo_utils::{get_mongo, init_mongo};
-    use common_lib::rabbit_utils::init_rabbitmq_with_config;
+    use common_lib::rabbit_utils::{get_rabbitmq_instance, init_rabbitmq_with_config};
     use common_lib::redis_handler::init_redis;
     use common_lib::redis_pool_utils::create_redis_pool_from_config;
 
@@ -213,7 +250,7 @@ mod tests {
             IdentificationCode: "102".to_string(),
             DataRows: vec![DataRow {
                 Name: "Temperature".to_string(),
-                Value: "2".to_string(),
+                Value: "2".into(),
             }],
             Nc: "1".to_string(),
             Protocol: Some("MQTT".to_string()),
@@ -222,11 +259,14 @@ mod tests {
         let pool = create_redis_pool_from_config(&config1.redis_config);
 
         let redisOp = RedisOp { pool };
+        let rabbit = get_rabbitmq_instance().await.unwrap();
+        let rabbit = rabbit.lock().await;
         handler_waring_delay_once(
             dt,
             "asf".to_string(),
             &redisOp,
             &get_mongo().await.unwrap().clone(),
+            &rabbit.channel,
         )
         .await
         .unwrap();
@@ -254,7 +294,7 @@ mod tests {
         );
 
         // 调用 call_js 函数
-        let result = call_js(js_code.to_string(), &param);
+        let result = call_js(1, js_code.to_string(), &param).unwrap();
         info!("{:?}", result);
         // 断言返回结果
         assert!(result); // 期望返回 true
@@ -263,10 +303,9 @@ mod tests {
 
 pub async fn handler_waring_delay_string(
     result: String,
-    jsc: Context,
     config: InfluxConfig,
     redis: &RedisOp,
-    rabbit_conn: &Connection,
+    channel: &Channel,
     script_waring_collection: String,
     mongo_dbmanager: &MongoDBManager,
 ) -> Result<(), Box<dyn Error>> {
@@ -276,9 +315,14 @@ pub async fn handler_waring_delay_string(
     let dt: Vec<DataRowList> = serde_json::from_str(&result)?;
 
     for x in dt {
-        handler_waring_delay_once(x, script_waring_collection.clone(), redis, mongo_dbmanager)
-            .await
-            .unwrap();
+        handler_waring_delay_once(
+            x,
+            script_waring_collection.clone(),
+            redis,
+            mongo_dbmanager,
+            channel,
+        )
+        .await?;
     }
 
     Ok(())
@@ -301,6 +345,9 @@ pub async fn waring_delay_handler(
         .await
         .unwrap();
 
+    // 报警事件发布通道, 所有消息共用
+    let publish_channel = rabbit_conn.create_channel().await.unwrap();
+
     trace!("rmq consumer connected, waiting for messages");
     while let Some(delivery_result) = consumer.next().await {
         match delivery_result {
@@ -311,10 +358,9 @@ pub async fn waring_delay_handler(
 
                 match handler_waring_delay_string(
                     result,
-                    Context::new().unwrap(),
                     influx_config.clone(),
                     guard,
-                    rabbit_conn,
+                    &publish_channel,
                     script_waring_collection.clone(),
                     mongo_dbmanager,
                 )
@@ -322,24 +368,28 @@ pub async fn waring_delay_handler(
                 {
                     Ok(_) => {
                         info!("msg processed");
+                        if let Err(e) = channel1
+                            .basic_ack(delivery.delivery_tag, BasicAckOptions::default())
+                            .await
+                        {
+                            error!("确认消息时发生错误: {}", e);
+                        }
                     }
                     Err(error) => {
-                        error!("{}", error);
+                        // Redis、Mongo、RabbitMQ 的临时错误重新投递一次, 再次失败时丢弃
+                        error!(
+                            "报警处理失败, redelivered = {}: {}",
+                            delivery.redelivered, error
+                        );
+                        let options = BasicNackOptions {
+                            requeue: !delivery.redelivered,
+                            ..Default::default()
+                        };
+                        if let Err(e) = channel1.basic_nack(delivery.delivery_tag, options).await {
+                            error!("拒绝消息时发生错误: {}", e);
+                        }
                     }
                 };
-
-                match channel1
-                    .basic_ack(delivery.delivery_tag, BasicAckOptions::default())
-                    .await
-                {
-                    Ok(_) => {
-                        info!("消息已成功确认。");
-                    }
-                    Err(e) => {
-                        error!("确认消息时发生错误: {}", e);
-                        // 这里可以添加进一步的错误处理逻辑
-                    }
-                }
             }
             Err(err) => {
                 error!("Error receiving message: {:?}", err);
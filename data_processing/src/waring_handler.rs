@@ -1,13 +1,23 @@
+use crate::alarm_state::{
+    alarm_state_key, commit_alarm_state, get_alarm_state, prepare_alarm_state,
+    record_alarm_transition,
+};
+use crate::escalation::schedule_escalation;
+use crate::silence::is_silenced;
+use crate::storage_handler::get_signal_samples;
 use chrono::Utc;
-use common_lib::models::{DataRowList, MQTTMessage, Signal, SignalWaringConfig};
+use common_lib::models::{
+    AlarmEvent, AlarmRuleType, AlarmStatus, DataRowList, MQTTMessage, Signal, SignalSamples,
+    SignalWaringConfig, ThresholdKind, Tv, ALARM_EXCHANGE,
+};
 use common_lib::redis_handler::RedisWrapper;
+use common_lib::unit_utils::convert_unit;
 use futures_util::StreamExt;
-use lapin::options::BasicAckOptions;
+use lapin::options::{BasicAckOptions, BasicNackOptions};
 use lapin::options::{BasicConsumeOptions, BasicPublishOptions};
 use lapin::types::FieldTable;
 use lapin::{BasicProperties, Channel, Connection};
-use log::{debug, error, info};
-use quick_js::{Context, ContextError};
+use log::{debug, error, info, warn};
 use std::collections::HashMap;
 use std::error::Error;
 use std::sync::Arc;
@@ -18,6 +28,7 @@ pub async fn handler_waring_once(
     waring_collection: String,
     redis: &RedisOp,
     mongo_dbmanager: &MongoDBManager,
+    channel: &Channel,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let device_uid_string = &*dt.DeviceUid;
     let iden_code = &*dt.IdentificationCode;
@@ -30,93 +41,310 @@ pub async fn handler_waring_once(
 
         let xdata = mapping.get(x.Name.as_str());
         if xdata.is_some() {
-            let x1 = xdata.unwrap();
+            let (signal_unit, x1) = xdata.unwrap();
             debug!("x1 = {:?}", x1);
 
-            let floatValue = x.Value.parse::<f64>().unwrap();
+            // 布尔值按 1/0 参与阈值判断
+            let floatValue = match x.Value.as_f64() {
+                Some(v) => v,
+                None => {
+                    debug!("信号 {} 的值 {} 不是数值, 跳过阈值判断", x.Name, x.Value);
+                    continue;
+                }
+            };
+
+            // 差值/变化率规则需要上一样本
+            let samples = match x1.iter().find(|c| c.kind != ThresholdKind::Range) {
+                Some(c) => {
+                    get_signal_samples(device_uid_string, iden_code, c.signal_id as i64, redis)?
+                }
+                None => None,
+            };
+            let prev = previous_sample(samples.as_ref(), push_time);
 
             for config in x1 {
                 let name = calc_collection_name(waring_collection.as_str(), config.id);
-                if config.in_or_out == 1 {
-                    if (config.min <= floatValue && floatValue <= config.max) {
-                        //     范围内
-                        let mut document = HashMap::new();
-                        document.insert(
-                            "device_uid".to_string(),
-                            serde_json::json!(device_uid_string),
-                        );
-                        document.insert("signal_name".to_string(), serde_json::json!(x.Name));
-                        document
-                            .insert("signal_id".to_string(), serde_json::json!(config.signal_id));
-                        document.insert("value".to_string(), serde_json::json!(floatValue));
-                        document.insert("rule_id".to_string(), serde_json::json!(config.id));
-                        document.insert("insert_time".to_string(), serde_json::json!(now));
-                        document.insert("up_time".to_string(), serde_json::json!(push_time));
-                        info!("命中报警 in_or_out = 1");
-
-                        mongo_dbmanager
-                            .insert_document(name.as_str(), document)
-                            .await
-                            .unwrap();
+                // 阈值配置的单位与信号单位不同时, 样本换算为阈值的单位再判断
+                let rule_unit = config.unit.as_deref();
+                // 单位无法换算时原值与阈值不可比较, 跳过该规则
+                let sample = match to_rule_unit(floatValue, signal_unit.as_deref(), rule_unit) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        warn!("rule {} 跳过: {}", config.id, e);
+                        continue;
                     }
-                } else {
-                    if (floatValue < config.min || floatValue > config.max) {
-                        //     范围外
-                        let mut document = HashMap::new();
-                        document.insert(
-                            "device_uid".to_string(),
-                            serde_json::json!(device_uid_string),
-                        );
-                        document.insert(
-                            "signal_name".to_string(),
-                            serde_json::json!(device_uid_string),
-                        );
-                        document.insert(
-                            "signal_id".to_string(),
-                            serde_json::json!(device_uid_string),
-                        );
-                        document.insert("value".to_string(), serde_json::json!(device_uid_string));
-                        document
-                            .insert("rule_id".to_string(), serde_json::json!(device_uid_string));
-                        document.insert(
-                            "insert_time".to_string(),
-                            serde_json::json!(device_uid_string),
-                        );
-                        document
-                            .insert("up_time".to_string(), serde_json::json!(device_uid_string));
-                        info!("命中报警 in_or_out = 0");
-                        mongo_dbmanager
-                            .create_collection(name.as_str())
-                            .await
-                            .unwrap();
-
-                        mongo_dbmanager
-                            .insert_document(name.as_str(), document)
-                            .await
-                            .unwrap();
+                };
+                let rule_prev = prev.as_ref().and_then(|tv| {
+                    to_rule_unit(tv.value, signal_unit.as_deref(), rule_unit)
+                        .ok()
+                        .map(|value| Tv {
+                            time: tv.time,
+                            value,
+                        })
+                });
+                let ruleValue = match rule_value(config, sample, push_time, rule_prev.as_ref()) {
+                    Some(v) => v,
+                    None => {
+                        debug!("rule {} 缺少历史样本, 跳过", config.id);
+                        continue;
                     }
-                }
-                // todo: message template
+                };
+                // 只有 FIRING/RESOLVED 状态迁移才产生报警记录
+                let state_key = alarm_state_key(
+                    AlarmRuleType::Threshold,
+                    config.id,
+                    device_uid_string,
+                    iden_code,
+                    config.signal_id,
+                );
+                let firing = get_alarm_state(state_key.as_str(), redis)?
+                    .map(|s| s.status == AlarmStatus::Firing)
+                    .unwrap_or(false);
+                let hit = threshold_hit(config, ruleValue, firing);
+                let hit = confirm_hit(state_key.as_str(), config, hit, firing, push_time, redis)?;
+
+                let update = match prepare_alarm_state(state_key.as_str(), hit, push_time, redis)? {
+                    Some(update) => update,
+                    None => continue,
+                };
+                let state = &update.state;
+                info!(
+                    "报警状态迁移 {:?} in_or_out = {}",
+                    state.status, config.in_or_out
+                );
+
+                let mut event = AlarmEvent {
+                    rule_id: config.id,
+                    rule_type: AlarmRuleType::Threshold,
+                    device_uid: device_uid_string.to_string(),
+                    identification_code: iden_code.to_string(),
+                    protocol: dt.Protocol.clone(),
+                    signal_id: Some(config.signal_id),
+                    signal_name: Some(x.Name.clone()),
+                    value: Some(ruleValue),
+                    param: None,
+                    time: push_time,
+                    insert_time: now,
+                    severity: config.severity,
+                    status: state.status,
+                    duration: state.resolved_at.map(|_| state.duration()),
+                    alarm_id: None,
+                    silenced: false,
+                    escalation_level: 0,
+                };
+                // 命中静默规则的报警照常记录和发布, 由通知服务跳过
+                event.silenced = is_silenced(&event, now, redis)?;
+                record_alarm_transition(state_key.as_str(), state, &mut event, mongo_dbmanager)
+                    .await?;
+
+                let mut document = HashMap::new();
+                document.insert(
+                    "device_uid".to_string(),
+                    serde_json::json!(device_uid_string),
+                );
+                document.insert("signal_name".to_string(), serde_json::json!(x.Name));
+                document.insert("signal_id".to_string(), serde_json::json!(config.signal_id));
+                document.insert("value".to_string(), serde_json::json!(ruleValue));
+                document.insert("sample_value".to_string(), serde_json::json!(sample));
+                document.insert("rule_id".to_string(), serde_json::json!(config.id));
+                document.insert("insert_time".to_string(), serde_json::json!(now));
+                document.insert("up_time".to_string(), serde_json::json!(push_time));
+                document.insert("severity".to_string(), serde_json::json!(event.severity));
+                document.insert("status".to_string(), serde_json::json!(event.status));
+                document.insert("fired_at".to_string(), serde_json::json!(state.fired_at));
+                document.insert("duration".to_string(), serde_json::json!(event.duration));
+                document.insert("silenced".to_string(), serde_json::json!(event.silenced));
+
+                mongo_dbmanager.create_collection(name.as_str()).await?;
+                mongo_dbmanager
+                    .insert_document(name.as_str(), document)
+                    .await?;
+
+                publish_alarm_event(channel, &event).await?;
+                schedule_escalation(&event, redis)?;
+                // 记录和发布成功后再提交状态, 失败时消息重新投递仍会重新处理该迁移
+                commit_alarm_state(state_key.as_str(), &update, redis)?;
             }
         }
     }
     Ok(())
 }
 
+/// 当前样本的上一样本, 时间均为设备上报时间
+///
+/// 入库在报警处理之前, 最近样本的上报时间与当前数据行相同时即为当前样本, 否则当前样本尚未入库.
+/// 早于最近样本的乱序数据没有可比较的上一样本
+fn previous_sample(samples: Option<&SignalSamples>, push_time: i64) -> Option<Tv> {
+    match samples {
+        Some(samples) if samples.last.time == push_time => samples.prev.clone(),
+        Some(samples) if samples.last.time < push_time => Some(samples.last.clone()),
+        _ => None,
+    }
+}
+
+/// 信号单位换算为阈值配置的单位, 任一单位未配置时使用原值, 单位未知或量纲不同时返回错误
+fn to_rule_unit(
+    value: f64,
+    signal_unit: Option<&str>,
+    rule_unit: Option<&str>,
+) -> Result<f64, String> {
+    let signal_unit = signal_unit.filter(|u| !u.trim().is_empty());
+    let rule_unit = rule_unit.filter(|u| !u.trim().is_empty());
+    match (signal_unit, rule_unit) {
+        (Some(from), Some(to)) => convert_unit(value, from, to),
+        _ => Ok(value),
+    }
+}
+
+/// 规则评估值: range 为当前值, delta 为与上一样本的差值, rate 为按 rate_unit 计的变化率
+///
+/// time 为当前样本的存储时间
+fn rule_value(
+    config: &SignalWaringConfig,
+    value: f64,
+    time: i64,
+    prev: Option<&Tv>,
+) -> Option<f64> {
+    if config.kind == ThresholdKind::Range {
+        return Some(value);
+    }
+
+    let prev = prev?;
+    let delta = value - prev.value;
+    if config.kind == ThresholdKind::Delta {
+        return Some(delta);
+    }
+
+    let elapsed = time - prev.time;
+    if elapsed <= 0 {
+        return None;
+    }
+    Some(delta / elapsed as f64 * config.rate_unit.seconds())
+}
+
+/// 阈值判定, 报警中使用恢复阈值 (clear_min/clear_max 或 min/max 外扩 deadband) 判定是否仍在报警
+fn threshold_hit(config: &SignalWaringConfig, value: f64, firing: bool) -> bool {
+    if config.in_or_out == 1 {
+        //     范围内
+        let (min, max) = if firing {
+            (
+                config.clear_min.unwrap_or(config.min - config.deadband),
+                config.clear_max.unwrap_or(config.max + config.deadband),
+            )
+        } else {
+            (config.min, config.max)
+        };
+        min <= value && value <= max
+    } else {
+        //     范围外
+        let (min, max) = if firing {
+            (
+                config.clear_min.unwrap_or(config.min + config.deadband),
+                config.clear_max.unwrap_or(config.max - config.deadband),
+            )
+        } else {
+            (config.min, config.max)
+        };
+        value < min || value > max
+    }
+}
+
+/// 待确认的命中, 存储在 alarm_pending 哈希中, 字段为报警状态键
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct PendingHit {
+    since: i64, // 首次命中时间
+    count: u32, // 连续命中次数
+}
+
+/// 持续时间/连续样本判定, 返回 (是否确认命中, 新的待确认状态)
+fn next_pending(
+    prev: Option<PendingHit>,
+    config: &SignalWaringConfig,
+    hit: bool,
+    firing: bool,
+    time: i64,
+) -> (bool, Option<PendingHit>) {
+    if firing || !hit {
+        return (hit, None);
+    }
+    let mut pending = prev.unwrap_or(PendingHit {
+        since: time,
+        count: 0,
+    });
+    pending.count += 1;
+
+    let hold_seconds_ok = config.hold_seconds <= 0 || time - pending.since >= config.hold_seconds;
+    let hold_samples_ok = config.hold_samples <= 1 || pending.count >= config.hold_samples;
+    if hold_seconds_ok && hold_samples_ok {
+        (true, None)
+    } else {
+        (false, Some(pending))
+    }
+}
+
+fn confirm_hit(
+    state_key: &str,
+    config: &SignalWaringConfig,
+    hit: bool,
+    firing: bool,
+    time: i64,
+    redis: &RedisOp,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let key = "alarm_pending";
+    let prev: Option<PendingHit> = match redis.get_hash(key, state_key)? {
+        Some(value) => serde_json::from_str(&value).ok(),
+        None => None,
+    };
+    let had_pending = prev.is_some();
+
+    let (confirmed, pending) = next_pending(prev, config, hit, firing, time);
+    match pending {
+        Some(pending) => {
+            debug!("alarm pending {} = {:?}", state_key, pending);
+            redis.set_hash(key, state_key, serde_json::to_string(&pending)?.as_str())?;
+        }
+        None if had_pending => redis.delete_hash_field(key, state_key)?,
+        None => {}
+    }
+    Ok(confirmed)
+}
+
+/// 发布报警事件到 ALARM_EXCHANGE, 路由键见 AlarmEvent::routing_key
+pub async fn publish_alarm_event(
+    channel: &Channel,
+    event: &AlarmEvent,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let message = event.to_json_string();
+    debug!("publish alarm event: {}", message);
+    channel
+        .basic_publish(
+            ALARM_EXCHANGE,
+            event.routing_key().as_str(),
+            BasicPublishOptions::default(),
+            message.as_bytes(),
+            BasicProperties::default(),
+        )
+        .await?;
+    Ok(())
+}
+
 pub fn calc_collection_name(prefix: &str, id: i32) -> String {
     let string = format!("{}_{}", prefix, id % 100);
     return string;
 }
 
+/// 信号单位及其阈值报警配置
+type SignalWaringConfigs = (Option<String>, Vec<SignalWaringConfig>);
+
 fn get_mapping_signal_waring_config(
     device_uid_string: &str,
     iden_code: &str,
     redis_wrapper: &RedisOp,
-) -> Result<HashMap<String, Vec<SignalWaringConfig>>, Box<dyn std::error::Error>> {
+) -> Result<HashMap<String, SignalWaringConfigs>, Box<dyn std::error::Error>> {
     let key = format!("signal:{}:{}", device_uid_string, iden_code);
     debug!("key = {}", key);
     let vec = redis_wrapper.get_list_all(key.as_str()).unwrap();
-    let mut mapping: HashMap<String, Vec<SignalWaringConfig>> = HashMap::new();
+    let mut mapping: HashMap<String, SignalWaringConfigs> = HashMap::new();
     for str_signal in vec {
         let signal: Signal = match serde_json::from_str(&str_signal) {
             Ok(s) => s,
@@ -143,17 +371,16 @@ fn get_mapping_signal_waring_config(
         }
         debug!("signal.name = {}", signal.name);
 
-        mapping.insert(signal.name, swcs);
+        mapping.insert(signal.name, (signal.unit, swcs));
     }
     Ok(mapping)
 }
 
 pub async fn handler_waring_string(
     result: String,
-    jsc: Context,
     config: InfluxConfig,
     redis: &RedisOp,
-    rabbit_conn: &Connection,
+    channel: &Channel,
     waring_collection: String,
     mongo_dbmanager: &MongoDBManager,
 ) -> Result<(), Box<dyn Error>> {
@@ -163,9 +390,14 @@ pub async fn handler_waring_string(
     let dt: Vec<DataRowList> = serde_json::from_str(&result)?;
 
     for x in dt {
-        handler_waring_once(x, waring_collection.clone(), redis, mongo_dbmanager)
-            .await
-            .unwrap();
+        handler_waring_once(
+            x,
+            waring_collection.clone(),
+            redis,
+            mongo_dbmanager,
+            channel,
+        )
+        .await?;
     }
 
     Ok(())
@@ -183,13 +415,128 @@ mod tests {
     use super::*;
     use common_lib::config::{get_config, read_config, read_config_tb};
     use common_lib::init_logger;
-    use common_lib::models::DataRow;
+    use common_lib::models::{DataRow, RateUnit};
     use common_lib::mongo_utils::init_mongo;
-    use common_lib::rabbit_utils::init_rabbitmq_with_config;
+    use common_lib::rabbit_utils::{get_rabbitmq_instance, init_rabbitmq_with_config};
     use common_lib::redis_handler::{get_redis_instance, init_redis};
     use common_lib::redis_pool_utils::create_redis_pool_from_config;
     use log::debug;
 
+    fn waring_config(in_or_out: i32) -> SignalWaringConfig {
+        serde_json::from_str(&format!(
+            r#"{{"signal_id":1,"min":10.0,"max":20.0,"in_or_out":{},"unit":null,"ID":1}}"#,
+            in_or_out
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_threshold_hit_deadband() {
+        let mut config = waring_config(0);
+        config.deadband = 1.0;
+        assert!(threshold_hit(&config, 20.5, false));
+        // 报警中需回到 [11, 19] 才恢复
+        assert!(threshold_hit(&config, 19.5, true));
+        assert!(!threshold_hit(&config, 18.5, true));
+
+        // 设置恢复阈值后不再使用 deadband
+        config.clear_max = Some(15.0);
+        assert!(threshold_hit(&config, 16.0, true));
+        assert!(!threshold_hit(&config, 14.0, true));
+
+        let mut config = waring_config(1);
+        config.deadband = 2.0;
+        assert!(!threshold_hit(&config, 21.0, false));
+        assert!(threshold_hit(&config, 21.0, true));
+        assert!(!threshold_hit(&config, 22.5, true));
+    }
+
+    #[test]
+    fn test_previous_sample() {
+        let tv = |time, value| Tv { time, value };
+        // 10@100, 4@110, 4@120: 重复值也保留各自的样本
+        let samples = SignalSamples {
+            last: tv(120, 4.0),
+            prev: Some(tv(110, 4.0)),
+        };
+
+        // 当前样本已入库, 与上一样本比较
+        assert_eq!(previous_sample(Some(&samples), 120), Some(tv(110, 4.0)));
+        // 当前样本尚未入库, 与最近样本比较
+        assert_eq!(previous_sample(Some(&samples), 125), Some(tv(120, 4.0)));
+        // 乱序数据
+        assert_eq!(previous_sample(Some(&samples), 115), None);
+        assert_eq!(previous_sample(None, 125), None);
+    }
+
+    #[test]
+    fn test_rule_value() {
+        let prev = Tv {
+            time: 100,
+            value: 10.0,
+        };
+
+        let mut config = waring_config(0);
+        assert_eq!(rule_value(&config, 4.0, 110, Some(&prev)), Some(4.0));
+        assert_eq!(rule_value(&config, 4.0, 110, None), Some(4.0));
+
+        config.kind = ThresholdKind::Delta;
+        assert_eq!(rule_value(&config, 4.0, 110, Some(&prev)), Some(-6.0));
+        assert_eq!(rule_value(&config, 10.0, 110, Some(&prev)), Some(0.0));
+        assert_eq!(rule_value(&config, 7.0, 120, None), None);
+
+        config.kind = ThresholdKind::Rate;
+        assert_eq!(rule_value(&config, 4.0, 110, Some(&prev)), Some(-0.6));
+        config.rate_unit = RateUnit::Minute;
+        assert_eq!(rule_value(&config, 4.0, 110, Some(&prev)), Some(-36.0));
+        assert_eq!(rule_value(&config, 7.0, 100, Some(&prev)), None);
+    }
+
+    #[test]
+    fn test_to_rule_unit() {
+        assert_eq!(to_rule_unit(100.0, Some("℃"), Some("K")), Ok(373.15));
+        assert_eq!(to_rule_unit(2.5, Some("kW"), Some("W")), Ok(2500.0));
+        // 未配置时使用原值, 单位未知或量纲不同时报错
+        assert_eq!(to_rule_unit(2.5, Some("kW"), Some("")), Ok(2.5));
+        assert_eq!(to_rule_unit(2.5, None, Some("W")), Ok(2.5));
+        assert!(to_rule_unit(2.5, Some("kW"), Some("A")).is_err());
+        assert!(to_rule_unit(2.5, Some("kW"), Some("foo")).is_err());
+    }
+
+    #[test]
+    fn test_next_pending_hold() {
+        let mut config = waring_config(0);
+        config.hold_samples = 3;
+        let (ok, pending) = next_pending(None, &config, true, false, 100);
+        assert!(!ok);
+        let (ok, pending) = next_pending(pending, &config, true, false, 101);
+        assert!(!ok);
+        assert_eq!(pending.as_ref().unwrap().count, 2);
+        let (ok, pending) = next_pending(pending, &config, true, false, 102);
+        assert!(ok);
+        assert!(pending.is_none());
+
+        // 中途未命中则重新计数
+        let (_, pending) = next_pending(None, &config, true, false, 100);
+        let (ok, pending) = next_pending(pending, &config, false, false, 101);
+        assert!(!ok);
+        assert!(pending.is_none());
+
+        let mut config = waring_config(0);
+        config.hold_seconds = 30;
+        let (ok, pending) = next_pending(None, &config, true, false, 100);
+        assert!(!ok);
+        let (ok, pending) = next_pending(pending, &config, true, false, 120);
+        assert!(!ok);
+        let (ok, _) = next_pending(pending, &config, true, false, 130);
+        assert!(ok);
+
+        // 报警中不做持续判定
+        let (ok, pending) = next_pending(None, &config, true, true, 100);
+        assert!(ok);
+        assert!(pending.is_none());
+    }
+
     #[tokio::test]
     async fn test_storage() {
         init_logger();
@@ -214,7 +561,7 @@ mod tests {
             IdentificationCode: "1".to_string(),
             DataRows: vec![DataRow {
                 Name: "信号-199".to_string(),
-                Value: "2".to_string(),
+                Value: "2".into(),
             }],
             Nc: "1".to_string(),
             Protocol: Some("MQTT".to_string()),
@@ -226,11 +573,14 @@ mod tests {
         let pool = create_redis_pool_from_config(&config1.redis_config);
 
         let redisOp = RedisOp { pool };
+        let rabbit = get_rabbitmq_instance().await.unwrap();
+        let rabbit = rabbit.lock().await;
         if let Err(e) = handler_waring_once(
             dt,
             mongo_config.waring_collection.unwrap(),
             &redisOp,
             &guard,
+            &rabbit.channel,
         )
         .await
         {
@@ -257,6 +607,9 @@ pub async fn waring_handler(
         .await
         .unwrap();
 
+    // 报警事件发布通道, 所有消息共用
+    let publish_channel = rabbit_conn.create_channel().await.unwrap();
+
     info!("rmq consumer connected, waiting for messages");
     while let Some(delivery_result) = consumer.next().await {
         match delivery_result {
@@ -267,10 +620,9 @@ pub async fn waring_handler(
 
                 match handler_waring_string(
                     result,
-                    Context::new().unwrap(),
                     influx_config.clone(),
                     guard,
-                    rabbit_conn,
+                    &publish_channel,
                     waring_collection.clone(),
                     mongo_dbmanager,
                 )
@@ -278,24 +630,28 @@ pub async fn waring_handler(
                 {
                     Ok(_) => {
                         info!("msg processed");
+                        if let Err(e) = channel1
+                            .basic_ack(delivery.delivery_tag, BasicAckOptions::default())
+                            .await
+                        {
+                            error!("确认消息时发生错误: {}", e);
+                        }
                     }
                     Err(error) => {
-                        error!("{}", error);
+                        // Redis、Mongo、RabbitMQ 的临时错误重新投递一次, 再次失败时丢弃
+                        error!(
+                            "报警处理失败, redelivered = {}: {}",
+                            delivery.redelivered, error
+                        );
+                        let options = BasicNackOptions {
+                            requeue: !delivery.redelivered,
+                            ..Default::default()
+                        };
+                        if let Err(e) = channel1.basic_nack(delivery.delivery_tag, options).await {
+                            error!("拒绝消息时发生错误: {}", e);
+                        }
                     }
                 };
-
-                match channel1
-                    .basic_ack(delivery.delivery_tag, BasicAckOptions::default())
-                    .await
-                {
-                    Ok(_) => {
-                        info!("消息已成功确认。");
-                    }
-                    Err(e) => {
-                        error!("确认消息时发生错误: {}", e);
-                        // 这里可以添加进一步的错误处理逻辑
-                    }
-                }
             }
             Err(err) => {
                 error!("Error receiving message: {:?}", err);
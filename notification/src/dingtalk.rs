@@ -0,0 +1,125 @@
+use crate::robot::RobotConfig;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::error::Error;
+
+/// 钉钉自定义机器人签名: 以 secret 为密钥对 "timestamp\nsecret" 做 HmacSHA256, 再 Base64
+pub fn sign(timestamp_ms: i64, secret: &str) -> String {
+    let string_to_sign = format!("{}\n{}", timestamp_ms, secret);
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(string_to_sign.as_bytes());
+    STANDARD.encode(mac.finalize().into_bytes())
+}
+
+/// 开启加签时, 在 webhook 地址后追加 timestamp 与 sign 参数
+pub fn sign_url(webhook: &str, secret: Option<&str>, timestamp_ms: i64) -> String {
+    match secret {
+        Some(secret) => {
+            let sep = if webhook.contains('?') { "&" } else { "?" };
+            format!(
+                "{}{}timestamp={}&sign={}",
+                webhook,
+                sep,
+                timestamp_ms,
+                urlencoding::encode(sign(timestamp_ms, secret).as_str())
+            )
+        }
+        None => webhook.to_string(),
+    }
+}
+
+pub async fn send_text(
+    client: &reqwest::Client,
+    robot: &RobotConfig,
+    text: &str,
+) -> Result<(), Box<dyn Error>> {
+    let url = sign_url(
+        robot.webhook.as_str(),
+        robot.secret.as_deref(),
+        Utc::now().timestamp_millis(),
+    );
+    let body = json!({
+        "msgtype": "text",
+        "text": { "content": text }
+    });
+    let response: Value = client
+        .post(url.as_str())
+        .json(&body)
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    let errcode = response
+        .get("errcode")
+        .and_then(|c| c.as_i64())
+        .unwrap_or(0);
+    if errcode != 0 {
+        return Err(format!("钉钉机器人返回错误: {}", response).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_lib::test_util::mock_server;
+
+    #[tokio::test]
+    async fn test_send_text_with_sign() {
+        let (url, requests) = mock_server(vec![], r#"{"errcode":0,"errmsg":"ok"}"#).await;
+        let robot = RobotConfig {
+            webhook: format!("{}/robot/send?access_token=token", url),
+            secret: Some("SEC000".to_string()),
+        };
+        send_text(&reqwest::Client::new(), &robot, "报警")
+            .await
+            .unwrap();
+
+        let requests = requests.lock().await;
+        assert_eq!(requests.len(), 1);
+        let request_line = requests[0].0.lines().next().unwrap();
+        let path = request_line.split(' ').nth(1).unwrap();
+        assert!(path.starts_with("/robot/send?access_token=token&timestamp="));
+
+        let query: Vec<(&str, &str)> = path
+            .split_once('?')
+            .unwrap()
+            .1
+            .split('&')
+            .map(|kv| kv.split_once('=').unwrap())
+            .collect();
+        let timestamp: i64 = query[1].1.parse().unwrap();
+        let sign_param = urlencoding::decode(query[2].1).unwrap();
+        assert_eq!(sign_param, sign(timestamp, "SEC000"));
+
+        let body: Value = serde_json::from_str(&requests[0].1).unwrap();
+        assert_eq!(body["msgtype"], "text");
+        assert_eq!(body["text"]["content"], "报警");
+    }
+
+    #[tokio::test]
+    async fn test_send_text_error_code() {
+        let (url, _) = mock_server(vec![], r#"{"errcode":310000,"errmsg":"sign not match"}"#).await;
+        let robot = RobotConfig {
+            webhook: url,
+            secret: None,
+        };
+        assert!(send_text(&reqwest::Client::new(), &robot, "报警")
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_sign_url_without_secret() {
+        assert_eq!(
+            sign_url("http://127.0.0.1/robot/send?access_token=t", None, 1),
+            "http://127.0.0.1/robot/send?access_token=t"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use crate::robot::RobotConfig;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::error::Error;
+
+/// 飞书自定义机器人签名: 以 "timestamp\nsecret" 为密钥对空串做 HmacSHA256, 再 Base64
+pub fn sign(timestamp: i64, secret: &str) -> String {
+    let string_to_sign = format!("{}\n{}", timestamp, secret);
+    let mac = Hmac::<Sha256>::new_from_slice(string_to_sign.as_bytes())
+        .expect("HMAC can take key of any size");
+    STANDARD.encode(mac.finalize().into_bytes())
+}
+
+pub fn text_body(text: &str, secret: Option<&str>, timestamp: i64) -> Value {
+    let mut body = json!({
+        "msg_type": "text",
+        "content": { "text": text }
+    });
+    if let Some(secret) = secret {
+        body["timestamp"] = json!(timestamp.to_string());
+        body["sign"] = json!(sign(timestamp, secret));
+    }
+    body
+}
+
+pub async fn send_text(
+    client: &reqwest::Client,
+    robot: &RobotConfig,
+    text: &str,
+) -> Result<(), Box<dyn Error>> {
+    let body = text_body(text, robot.secret.as_deref(), Utc::now().timestamp());
+    let response: Value = client
+        .post(robot.webhook.as_str())
+        .json(&body)
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    // 新版接口返回 code, 旧版返回 StatusCode
+    let code = response
+        .get("code")
+        .or_else(|| response.get("StatusCode"))
+        .and_then(|c| c.as_i64())
+        .unwrap_or(0);
+    if code != 0 {
+        return Err(format!("飞书机器人返回错误: {}", response).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_lib::test_util::mock_server;
+
+    #[tokio::test]
+    async fn test_send_text_with_sign() {
+        let (url, requests) = mock_server(vec![], r#"{"code":0,"msg":"success"}"#).await;
+        let robot = RobotConfig {
+            webhook: format!("{}/open-apis/bot/v2/hook/token", url),
+            secret: Some("secret".to_string()),
+        };
+        send_text(&reqwest::Client::new(), &robot, "报警")
+            .await
+            .unwrap();
+
+        let requests = requests.lock().await;
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0]
+            .0
+            .starts_with("POST /open-apis/bot/v2/hook/token "));
+        let body: Value = serde_json::from_str(&requests[0].1).unwrap();
+        assert_eq!(body["msg_type"], "text");
+        assert_eq!(body["content"]["text"], "报警");
+        let timestamp: i64 = body["timestamp"].as_str().unwrap().parse().unwrap();
+        assert_eq!(body["sign"], sign(timestamp, "secret"));
+    }
+
+    #[tokio::test]
+    async fn test_send_text_error_code() {
+        let (url, _) = mock_server(vec![], r#"{"code":19021,"msg":"sign match fail"}"#).await;
+        let robot = RobotConfig {
+            webhook: url,
+            secret: None,
+        };
+        assert!(send_text(&reqwest::Client::new(), &robot, "报警")
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_text_body_without_secret() {
+        let body = text_body("hi", None, 1730000000);
+        assert!(body.get("sign").is_none());
+        assert_eq!(sign(1730000000, "secret").len(), 44);
+    }
+}
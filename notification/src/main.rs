@@ -1,3 +1,61 @@
-fn main() {
-    println!("Hello, world!");
+use crate::notice_handler::notice_handler;
+use common_lib::config::read_config_tb;
+use common_lib::init_logger;
+use common_lib::models::ALARM_EXCHANGE;
+use common_lib::rabbit_utils::declare_alarm_exchange;
+use common_lib::redis_pool_utils::{create_redis_pool_from_config, RedisOp};
+use lapin::options::{QueueBindOptions, QueueDeclareOptions};
+use lapin::types::FieldTable;
+use lapin::{Connection, ConnectionProperties};
+use log::info;
+
+mod dingtalk;
+mod feishu;
+mod notice_handler;
+mod robot;
+mod template;
+
+#[tokio::main]
+async fn main() {
+    init_logger();
+    let config = read_config_tb("app-local.yml");
+
+    let pool = create_redis_pool_from_config(&config.redis_config);
+    let redis_op = RedisOp { pool };
+
+    let url = format!(
+        "amqp://{}:{}@{}:{}",
+        config.mq_config.username,
+        config.mq_config.password,
+        config.mq_config.host,
+        config.mq_config.port
+    );
+    let connection = Connection::connect(url.as_str(), ConnectionProperties::default())
+        .await
+        .unwrap();
+    let channel = connection.create_channel().await.unwrap();
+
+    // 报警消息队列, 绑定报警事件交换机接收全部报警
+    channel
+        .queue_declare(
+            "waring_notice",
+            QueueDeclareOptions::default(),
+            FieldTable::default(),
+        )
+        .await
+        .unwrap();
+    declare_alarm_exchange(&channel).await.unwrap();
+    channel
+        .queue_bind(
+            "waring_notice",
+            ALARM_EXCHANGE,
+            "alarm.#",
+            QueueBindOptions::default(),
+            FieldTable::default(),
+        )
+        .await
+        .unwrap();
+
+    info!("notification started: {}", config.node_info.name);
+    notice_handler(&redis_op, &channel).await;
 }
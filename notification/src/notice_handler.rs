@@ -0,0 +1,170 @@
+use crate::robot::{send_text, NoticeBind, RobotConfig};
+use crate::template::{default_template, render};
+use common_lib::models::{AlarmEvent, EscalationPolicy};
+use common_lib::redis_pool_utils::RedisOp;
+use futures_util::StreamExt;
+use lapin::options::{BasicAckOptions, BasicConsumeOptions};
+use lapin::types::FieldTable;
+use lapin::Channel;
+use log::{debug, error, info};
+use serde_json::Value;
+use std::error::Error;
+
+pub async fn handler_notice_string(
+    result: String,
+    redis: &RedisOp,
+    client: &reqwest::Client,
+) -> Result<(), Box<dyn Error>> {
+    info!("message : {:?}", result);
+
+    let event: AlarmEvent = serde_json::from_str(&result)?;
+    // 静默事件只供转发和界面使用, 静默结束后仍在报警时会重新发布
+    if event.silenced {
+        debug!(
+            "skip silenced alarm event {}:{}",
+            event.rule_type.as_str(),
+            event.rule_id
+        );
+        return Ok(());
+    }
+    let message: Value = serde_json::to_value(&event)?;
+    let rule_id = event.rule_id.to_string();
+
+    // 升级通知发送给升级策略对应层级, 否则发送给规则绑定的机器人
+    let binds = if event.escalation_level > 0 {
+        get_escalation_bind(
+            event.rule_type.as_str(),
+            rule_id.as_str(),
+            event.escalation_level,
+            redis,
+        )?
+    } else {
+        get_notice_bind(event.rule_type.as_str(), rule_id.as_str(), redis)?
+    };
+
+    for bind in binds {
+        let robot = match get_robot_config(bind.robot_type.as_str(), bind.robot_id, redis)? {
+            Some(robot) => robot,
+            None => {
+                error!("机器人配置不存在: {}:{}", bind.robot_type, bind.robot_id);
+                continue;
+            }
+        };
+
+        let text = render(
+            bind.template
+                .as_deref()
+                .unwrap_or(default_template(event.status)),
+            &message,
+        );
+        // 单个机器人失败不影响其他机器人
+        match send_text(client, bind.robot_type.as_str(), &robot, text.as_str()).await {
+            Ok(_) => info!("通知已发送 {}:{}", bind.robot_type, bind.robot_id),
+            Err(e) => error!("通知发送失败 {}:{}: {}", bind.robot_type, bind.robot_id, e),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn get_notice_bind(
+    rule_type: &str,
+    rule_id: &str,
+    redis: &RedisOp,
+) -> Result<Vec<NoticeBind>, Box<dyn Error>> {
+    let key = format!("notice_bind:{}:{}", rule_type, rule_id);
+    debug!("key = {}", key);
+
+    let mut binds = Vec::new();
+    for value in redis.get_list_all(key.as_str())? {
+        match serde_json::from_str::<NoticeBind>(&value) {
+            Ok(bind) => binds.push(bind),
+            Err(e) => {
+                error!("通知规则反序列化失败: {}", e);
+            }
+        }
+    }
+    Ok(binds)
+}
+
+pub fn get_escalation_bind(
+    rule_type: &str,
+    rule_id: &str,
+    level: u32,
+    redis: &RedisOp,
+) -> Result<Vec<NoticeBind>, Box<dyn Error>> {
+    let field = format!("{}:{}", rule_type, rule_id);
+    let policy: EscalationPolicy = match redis.get_hash("escalation_policy", field.as_str())? {
+        Some(value) => serde_json::from_str(&value)?,
+        None => return Ok(vec![]),
+    };
+
+    match policy.tiers.into_iter().nth(level as usize - 1) {
+        Some(tier) => Ok(tier.notice),
+        None => {
+            error!("升级策略 {} 不存在第 {} 层级", field, level);
+            Ok(vec![])
+        }
+    }
+}
+
+pub fn get_robot_config(
+    robot_type: &str,
+    robot_id: i64,
+    redis: &RedisOp,
+) -> Result<Option<RobotConfig>, Box<dyn Error>> {
+    let key = format!("notice:{}", robot_type);
+    match redis.get_hash(key.as_str(), robot_id.to_string().as_str())? {
+        Some(value) => Ok(Some(serde_json::from_str(&value)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn notice_handler(guard: &RedisOp, channel1: &Channel) {
+    let client = reqwest::Client::new();
+
+    let mut consumer = channel1
+        .basic_consume(
+            "waring_notice",
+            "",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await
+        .unwrap();
+
+    info!("rmq consumer connected, waiting for messages");
+    while let Some(delivery_result) = consumer.next().await {
+        match delivery_result {
+            Ok(delivery) => {
+                info!("received msg: {:?}", delivery);
+
+                let result = String::from_utf8(delivery.data).unwrap();
+
+                match handler_notice_string(result, guard, &client).await {
+                    Ok(_) => {
+                        info!("msg processed");
+                    }
+                    Err(error) => {
+                        error!("{}", error);
+                    }
+                };
+
+                match channel1
+                    .basic_ack(delivery.delivery_tag, BasicAckOptions::default())
+                    .await
+                {
+                    Ok(_) => {
+                        info!("消息已成功确认。");
+                    }
+                    Err(e) => {
+                        error!("确认消息时发生错误: {}", e);
+                    }
+                }
+            }
+            Err(err) => {
+                error!("Error receiving message: {:?}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use crate::{dingtalk, feishu};
+pub use common_lib::models::NoticeBind;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// 自定义机器人配置
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RobotConfig {
+    pub webhook: String,        // 机器人 webhook 地址
+    pub secret: Option<String>, // 加签密钥, 未开启加签时为空
+}
+
+pub async fn send_text(
+    client: &reqwest::Client,
+    robot_type: &str,
+    robot: &RobotConfig,
+    text: &str,
+) -> Result<(), Box<dyn Error>> {
+    match robot_type {
+        "feishu" => feishu::send_text(client, robot, text).await,
+        "dingtalk" => dingtalk::send_text(client, robot, text).await,
+        _ => Err(format!("unsupported robot type: {}", robot_type).into()),
+    }
+}
@@ -0,0 +1,81 @@
+use common_lib::models::AlarmStatus;
+use serde_json::Value;
+
+/// 默认报警消息模板
+pub const DEFAULT_TEMPLATE: &str =
+    "【报警】设备 {{device_uid}} 信号 {{signal_name}} 当前值 {{value}}, 命中规则 {{rule_id}}";
+
+/// 默认恢复消息模板
+pub const RESOLVED_TEMPLATE: &str =
+    "【恢复】设备 {{device_uid}} 信号 {{signal_name}} 当前值 {{value}}, 规则 {{rule_id}} 已恢复";
+
+/// 未配置模板时按报警状态选择默认模板
+pub fn default_template(status: AlarmStatus) -> &'static str {
+    match status {
+        AlarmStatus::Firing => DEFAULT_TEMPLATE,
+        AlarmStatus::Resolved => RESOLVED_TEMPLATE,
+    }
+}
+
+/// 渲染消息模板, `{{name}}` 替换为消息中同名字段, 支持 `{{a.b}}` 访问嵌套字段,
+/// 不存在的字段替换为空字符串
+pub fn render(template: &str, message: &Value) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                let name = after[..end].trim();
+                result.push_str(lookup(message, name).as_str());
+                rest = &after[end + 2..];
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn lookup(message: &Value, name: &str) -> String {
+    let pointer = format!("/{}", name.replace('.', "/"));
+    match message.pointer(pointer.as_str()) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(v) => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render() {
+        let message = json!({
+            "device_uid": "1",
+            "signal_name": "Temperature",
+            "value": 35.5,
+            "rule_id": 3,
+            "extra": {"level": "high"}
+        });
+        assert_eq!(
+            render(DEFAULT_TEMPLATE, &message),
+            "【报警】设备 1 信号 Temperature 当前值 35.5, 命中规则 3"
+        );
+        assert_eq!(
+            render(default_template(AlarmStatus::Resolved), &message),
+            "【恢复】设备 1 信号 Temperature 当前值 35.5, 规则 3 已恢复"
+        );
+        assert_eq!(
+            render("{{ extra.level }}-{{missing}}-{{unclosed", &message),
+            "high--{{unclosed"
+        );
+    }
+}